@@ -0,0 +1,221 @@
+use mask::SPECIAL_CHARACTERS;
+use error::{AlphabetError, CombinationError};
+
+/// An ordered list of the symbols a passcode can be made of, along
+/// with the numerical value each symbol takes on inside of a rule.
+///
+/// Combinations are stored as symbol values, so a hexadecimal `f`
+/// is compared and added as the number 15.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Alphabet {
+    symbols: Vec<String>,
+    values: Vec<u8>,
+}
+
+impl Alphabet {
+    /// Builds an alphabet out of the given symbols, where each symbol's
+    /// value is its position in the list.
+    ///
+    /// ```
+    /// use passcode_cracker::Alphabet;
+    ///
+    /// let alphabet = Alphabet::new(&["x", "y", "z"]).unwrap();
+    /// assert_eq!(alphabet.symbol_value("z"), Some(2));
+    /// ```
+    pub fn new(symbols: &[&str]) -> Result<Alphabet, AlphabetError> {
+        if symbols.len() > 256 {
+            return Err(AlphabetError::TooManySymbols(symbols.len()));
+        }
+        let pairs: Vec<(&str, u8)> = symbols.iter().enumerate().map(|(i, s)| (*s, i as u8)).collect();
+        Alphabet::with_values(&pairs)
+    }
+
+    /// Builds an alphabet where every character of `symbols` is a
+    /// separate symbol, valued by its position in the string.
+    ///
+    /// ```
+    /// use passcode_cracker::Alphabet;
+    ///
+    /// let alphabet = Alphabet::from_chars("ABC").unwrap();
+    /// assert_eq!(alphabet.len(), 3);
+    /// ```
+    pub fn from_chars(symbols: &str) -> Result<Alphabet, AlphabetError> {
+        let symbols: Vec<String> = symbols.chars().map(|c| c.to_string()).collect();
+        let symbols: Vec<&str> = symbols.iter().map(|s| s.as_str()).collect();
+        Alphabet::new(&symbols)
+    }
+
+    /// Builds an alphabet out of explicit symbol and value pairs. Both
+    /// the symbols and the values must be unique.
+    ///
+    /// ```
+    /// use passcode_cracker::Alphabet;
+    ///
+    /// let alphabet = Alphabet::with_values(&[("low", 1), ("high", 9)]).unwrap();
+    /// assert_eq!(alphabet.symbol_for(9), Some("high"));
+    /// ```
+    pub fn with_values(pairs: &[(&str, u8)]) -> Result<Alphabet, AlphabetError> {
+        if pairs.is_empty() {
+            return Err(AlphabetError::NoSymbols);
+        }
+        let mut symbols: Vec<String> = Vec::with_capacity(pairs.len());
+        let mut values: Vec<u8> = Vec::with_capacity(pairs.len());
+        for &(symbol, value) in pairs.iter() {
+            if symbol.is_empty() {
+                return Err(AlphabetError::EmptySymbol);
+            }
+            if symbols.iter().any(|s| s == symbol) {
+                return Err(AlphabetError::DuplicateSymbol(symbol.to_owned()));
+            }
+            if values.contains(&value) {
+                return Err(AlphabetError::DuplicateValue(value));
+            }
+            symbols.push(symbol.to_owned());
+            values.push(value);
+        }
+        Ok(Alphabet { symbols, values })
+    }
+
+    /// The digits 0 through 9.
+    pub fn decimal() -> Alphabet {
+        Alphabet::from_chars("0123456789").unwrap()
+    }
+
+    /// The digits 0 through 9 followed by the letters a through f,
+    /// valued 10 through 15.
+    pub fn hexadecimal() -> Alphabet {
+        Alphabet::from_chars("0123456789abcdef").unwrap()
+    }
+
+    /// The letters a through z, valued 0 through 25.
+    pub fn lowercase() -> Alphabet {
+        Alphabet::from_chars("abcdefghijklmnopqrstuvwxyz").unwrap()
+    }
+
+    /// Every printable ASCII character: the digits valued 0 through 9,
+    /// the lowercase letters valued 10 through 35 (so `a` through `f`
+    /// match their hexadecimal value), the uppercase letters valued 36
+    /// through 61 and finally the special characters.
+    pub fn printable() -> Alphabet {
+        let symbols = format!("0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ{}", SPECIAL_CHARACTERS);
+        Alphabet::from_chars(&symbols).unwrap()
+    }
+
+    /// The numbers 0 through `size - 1`, as found on a rotary dial.
+    /// Like any alphabet, a dial has at most 256 numbers.
+    ///
+    /// ```
+    /// use passcode_cracker::Alphabet;
+    ///
+    /// assert_eq!(Alphabet::dial(40).unwrap().format(&[12, 30, 8]), "12-30-8");
+    /// assert!(Alphabet::dial(300).is_err());
+    /// ```
+    pub fn dial(size: usize) -> Result<Alphabet, AlphabetError> {
+        if size > 256 {
+            return Err(AlphabetError::TooManySymbols(size));
+        }
+        let symbols: Vec<String> = (0..size).map(|n| n.to_string()).collect();
+        let symbols: Vec<&str> = symbols.iter().map(|s| s.as_str()).collect();
+        Alphabet::new(&symbols)
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// The value of every symbol, in alphabet order.
+    pub fn values(&self) -> &[u8] {
+        &self.values
+    }
+
+    pub fn symbol_value(&self, symbol: &str) -> Option<u8> {
+        self.symbols.iter().position(|s| s == symbol).map(|i| self.values[i])
+    }
+
+    /// Where the symbol sits in the alphabet's order.
+    pub fn index_of(&self, symbol: &str) -> Option<usize> {
+        self.symbols.iter().position(|s| s == symbol)
+    }
+
+    pub fn symbol(&self, index: usize) -> Option<&str> {
+        self.symbols.get(index).map(|s| s.as_str())
+    }
+
+    pub fn symbol_for(&self, value: u8) -> Option<&str> {
+        self.values.iter().position(|v| *v == value).map(|i| self.symbols[i].as_str())
+    }
+
+    /// Renders a combination of symbol values using the alphabet's
+    /// symbols. Single character symbols are printed side by side,
+    /// longer symbols are separated with dashes.
+    ///
+    /// ```
+    /// use passcode_cracker::Alphabet;
+    ///
+    /// assert_eq!(Alphabet::hexadecimal().format(&[1, 15, 10]), "1fa");
+    /// ```
+    pub fn format(&self, combination: &[u8]) -> String {
+        let separator = if self.symbols.iter().all(|s| s.chars().count() == 1) { "" } else { "-" };
+        let symbols: Vec<String> = combination.iter().map(|v| match self.symbol_for(*v) {
+            Some(symbol) => symbol.to_owned(),
+            None => format!("<{}>", v),
+        }).collect();
+        symbols.join(separator)
+    }
+
+    /// Reads a combination written the way `format` writes it, with
+    /// single character symbols side by side and longer symbols
+    /// separated by dashes.
+    ///
+    /// ```
+    /// use passcode_cracker::Alphabet;
+    ///
+    /// assert_eq!(Alphabet::hexadecimal().parse("1fa"), Ok(vec![1, 15, 10]));
+    /// assert_eq!(Alphabet::dial(40).unwrap().parse("12-30-8"), Ok(vec![12, 30, 8]));
+    /// assert!(Alphabet::decimal().parse("12a").is_err());
+    /// ```
+    pub fn parse(&self, text: &str) -> Result<Vec<u8>, CombinationError> {
+        let symbols: Vec<String> = if self.symbols.iter().all(|s| s.chars().count() == 1) {
+            text.chars().map(|c| c.to_string()).collect()
+        } else {
+            text.split('-').map(|s| s.trim().to_owned()).collect()
+        };
+        symbols.iter().map(|symbol| self.symbol_value(symbol).ok_or_else(|| CombinationError::UnknownSymbol(symbol.clone()))).collect()
+    }
+}
+
+impl Default for Alphabet {
+    fn default() -> Alphabet {
+        Alphabet::decimal()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_duplicates() {
+        assert!(Alphabet::new(&["1", "2", "1"]).is_err());
+        assert!(Alphabet::with_values(&[("x", 1), ("y", 1)]).is_err());
+        assert!(Alphabet::new(&[]).is_err());
+    }
+
+    #[test]
+    fn format_multi_character_symbols() {
+        let alphabet = Alphabet::new(&["10", "20", "30"]).unwrap();
+        assert_eq!(alphabet.format(&[2, 0, 1]), "30-10-20");
+    }
+
+    #[test]
+    fn hexadecimal_values() {
+        let alphabet = Alphabet::hexadecimal();
+        assert_eq!(alphabet.len(), 16);
+        assert_eq!(alphabet.symbol_value("a"), Some(10));
+        assert_eq!(alphabet.symbol_value("g"), None);
+    }
+}
@@ -0,0 +1,438 @@
+use ast::evaluable::{Value, Evaluable};
+use ast::expression::Expression;
+use ast::operator::{Operator, BinaryOperator, BooleanOperator};
+use ast::predicate::Predicate;
+use batch::Batch;
+
+/// A rule compiled into a flat program for a stack machine, so checking
+/// a combination against it is a single loop over instructions instead
+/// of a walk through the expression tree. Booleans live on the stack as
+/// 0 and 1. Anything the tree would fail to evaluate, like a missing
+/// position or an overflow, stops the program without a result.
+#[derive(Debug, Clone)]
+pub struct Program {
+    code: Vec<Instruction>,
+    depth: usize,
+}
+
+#[derive(Debug, Clone)]
+enum Instruction {
+    Position(usize),
+    FromEnd(usize),
+    Length,
+    Constant(i64),
+    Predicate(Predicate),
+    Arithmetic(Arithmetic),
+    /// Arithmetic with a constant right hand side.
+    ArithmeticWith(Arithmetic, i64),
+    /// Wraps the top of the stack around a dial of this size.
+    Wrap(i64),
+    Compare(Comparison),
+    /// A comparison with a constant right hand side.
+    CompareWith(Comparison, i64),
+    Not,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Arithmetic {
+    Add,
+    Subtract,
+    Multiply,
+    Modulo,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Comparison {
+    Equal,
+    LessThan,
+    GreaterThan,
+}
+
+/// Programs no deeper than this run on a stack that lives on the call
+/// stack, rather than one that has to be allocated.
+const STACK_SIZE: usize = 16;
+
+impl Program {
+    pub fn compile(expression: &Expression) -> Program {
+        let mut code = Vec::new();
+        emit(expression, &mut code);
+        let mut depth: usize = 0;
+        let mut max_depth = 0;
+        for instruction in code.iter() {
+            depth = match *instruction {
+                Instruction::Position(_) | Instruction::FromEnd(_) | Instruction::Length
+                    | Instruction::Constant(_) | Instruction::Predicate(_) => depth + 1,
+                Instruction::Arithmetic(_) | Instruction::Compare(_) => depth - 1,
+                _ => depth,
+            };
+            max_depth = max_depth.max(depth);
+        }
+        Program { code, depth: max_depth }
+    }
+
+    /// Whether the rule holds for the combination.
+    pub fn holds(&self, arr: &[u8]) -> bool {
+        self.evaluate(arr) == Some(1)
+    }
+
+    /// What the program leaves on the stack, or `None` if it stopped.
+    pub fn evaluate(&self, arr: &[u8]) -> Option<i64> {
+        if self.depth <= STACK_SIZE {
+            self.execute(arr, &mut [0; STACK_SIZE])
+        } else {
+            self.execute(arr, &mut vec![0; self.depth])
+        }
+    }
+
+    fn execute(&self, arr: &[u8], stack: &mut [i64]) -> Option<i64> {
+        let mut top = 0;
+        for instruction in self.code.iter() {
+            match *instruction {
+                Instruction::Position(i) => {
+                    stack[top] = *arr.get(i)? as i64;
+                    top += 1;
+                },
+                Instruction::FromEnd(offset) => {
+                    if offset < 1 || offset > arr.len() {
+                        return None;
+                    }
+                    stack[top] = arr[arr.len() - offset] as i64;
+                    top += 1;
+                },
+                Instruction::Length => {
+                    stack[top] = arr.len() as i64;
+                    top += 1;
+                },
+                Instruction::Constant(value) => {
+                    stack[top] = value;
+                    top += 1;
+                },
+                Instruction::Predicate(ref predicate) => {
+                    stack[top] = match predicate.evaluate(arr).ok()? {
+                        Value::Numerical(n) => n,
+                        Value::Boolean(b) => b as i64,
+                    };
+                    top += 1;
+                },
+                Instruction::Arithmetic(op) => {
+                    top -= 1;
+                    stack[top - 1] = op.apply(stack[top - 1], stack[top])?;
+                },
+                Instruction::ArithmeticWith(op, r) => stack[top - 1] = op.apply(stack[top - 1], r)?,
+                Instruction::Wrap(modulus) => stack[top - 1] = stack[top - 1].rem_euclid(modulus),
+                Instruction::Compare(cmp) => {
+                    top -= 1;
+                    stack[top - 1] = cmp.apply(stack[top - 1], stack[top]) as i64;
+                },
+                Instruction::CompareWith(cmp, r) => stack[top - 1] = cmp.apply(stack[top - 1], r) as i64,
+                Instruction::Not => stack[top - 1] = 1 - stack[top - 1],
+            }
+        }
+        if top == 1 { Some(stack[0]) } else { None }
+    }
+}
+
+impl Program {
+    /// Checks every candidate of a batch at once, one instruction at a
+    /// time over whole columns, clearing the flags of the candidates
+    /// the rule doesn't hold for.
+    pub fn holds_batch(&self, batch: &Batch, keep: &mut [bool]) {
+        let n = batch.len();
+        let lengths = &batch.lengths[..n];
+        let mut stack: Vec<Vec<i64>> = vec![vec![0; n]; self.depth];
+        let mut valid: Vec<bool> = vec![true; n];
+        let mut row: Vec<u8> = Vec::new();
+        let mut top = 0;
+        for instruction in self.code.iter() {
+            match *instruction {
+                Instruction::Position(i) => {
+                    match batch.columns.get(i) {
+                        Some(column) => {
+                            for (value, x) in stack[top].iter_mut().zip(column[..n].iter()) {
+                                *value = *x as i64;
+                            }
+                            for (valid, length) in valid.iter_mut().zip(lengths.iter()) {
+                                *valid &= (*length as usize) > i;
+                            }
+                        },
+                        None => valid.iter_mut().for_each(|valid| *valid = false),
+                    }
+                    top += 1;
+                },
+                Instruction::FromEnd(offset) => {
+                    for k in 0..n {
+                        let length = lengths[k] as usize;
+                        if offset >= 1 && offset <= length {
+                            stack[top][k] = batch.columns[length - offset][k] as i64;
+                        } else {
+                            valid[k] = false;
+                        }
+                    }
+                    top += 1;
+                },
+                Instruction::Length => {
+                    for (value, length) in stack[top].iter_mut().zip(lengths.iter()) {
+                        *value = *length as i64;
+                    }
+                    top += 1;
+                },
+                Instruction::Constant(constant) => {
+                    stack[top].iter_mut().for_each(|value| *value = constant);
+                    top += 1;
+                },
+                Instruction::Predicate(ref predicate) => {
+                    for k in 0..n {
+                        batch.row(k, &mut row);
+                        match predicate.evaluate(&row) {
+                            Ok(Value::Numerical(value)) => stack[top][k] = value,
+                            Ok(Value::Boolean(b)) => stack[top][k] = b as i64,
+                            Err(_) => valid[k] = false,
+                        }
+                    }
+                    top += 1;
+                },
+                Instruction::Arithmetic(op) => {
+                    top -= 1;
+                    let (lower, upper) = stack.split_at_mut(top);
+                    op.apply_columns(&mut lower[top - 1], &upper[0], &mut valid);
+                },
+                Instruction::ArithmeticWith(op, r) => {
+                    let constant = vec![r; n];
+                    op.apply_columns(&mut stack[top - 1], &constant, &mut valid);
+                },
+                Instruction::Wrap(modulus) => stack[top - 1].iter_mut().for_each(|value| *value = value.rem_euclid(modulus)),
+                Instruction::Compare(cmp) => {
+                    top -= 1;
+                    let (lower, upper) = stack.split_at_mut(top);
+                    cmp.apply_columns(&mut lower[top - 1], &upper[0]);
+                },
+                Instruction::CompareWith(cmp, r) => {
+                    let constant = vec![r; n];
+                    cmp.apply_columns(&mut stack[top - 1], &constant);
+                },
+                Instruction::Not => stack[top - 1].iter_mut().for_each(|value| *value = 1 - *value),
+            }
+        }
+        for ((keep, valid), value) in keep.iter_mut().zip(valid.iter()).zip(stack[0].iter()) {
+            *keep &= *valid && *value == 1;
+        }
+    }
+}
+
+impl Arithmetic {
+    fn apply(self, l: i64, r: i64) -> Option<i64> {
+        match self {
+            Arithmetic::Add => l.checked_add(r),
+            Arithmetic::Subtract => l.checked_sub(r),
+            Arithmetic::Multiply => l.checked_mul(r),
+            Arithmetic::Modulo => l.checked_rem_euclid(r),
+        }
+    }
+
+    /// Applies the operation down two columns, clearing `valid` where
+    /// it fails.
+    fn apply_columns(self, l: &mut [i64], r: &[i64], valid: &mut [bool]) {
+        match self {
+            Arithmetic::Add => zip_columns(l, r, valid, |l, r| l.overflowing_add(r)),
+            Arithmetic::Subtract => zip_columns(l, r, valid, |l, r| l.overflowing_sub(r)),
+            Arithmetic::Multiply => zip_columns(l, r, valid, |l, r| l.overflowing_mul(r)),
+            Arithmetic::Modulo => zip_columns(l, r, valid, |l, r| match l.checked_rem_euclid(r) {
+                Some(result) => (result, false),
+                None => (0, true),
+            }),
+        }
+    }
+}
+
+/// Sets each `l` to `f(l, r)`, which also says whether it failed.
+#[inline(always)]
+fn zip_columns<F: Fn(i64, i64) -> (i64, bool)>(l: &mut [i64], r: &[i64], valid: &mut [bool], f: F) {
+    for ((l, r), valid) in l.iter_mut().zip(r.iter()).zip(valid.iter_mut()) {
+        let (result, failed) = f(*l, *r);
+        *l = result;
+        *valid &= !failed;
+    }
+}
+
+impl Comparison {
+    fn apply(self, l: i64, r: i64) -> bool {
+        match self {
+            Comparison::Equal => l == r,
+            Comparison::LessThan => l < r,
+            Comparison::GreaterThan => l > r,
+        }
+    }
+
+    fn apply_columns(self, l: &mut [i64], r: &[i64]) {
+        for (l, r) in l.iter_mut().zip(r.iter()) {
+            *l = self.apply(*l, *r) as i64;
+        }
+    }
+}
+
+/// Appends the instructions for an expression in postfix order, folding
+/// the parts that don't read the combination into constants.
+fn emit(expression: &Expression, code: &mut Vec<Instruction>) {
+    if let Some(value) = constant(expression) {
+        code.push(Instruction::Constant(value));
+        return;
+    }
+    match *expression {
+        Expression::Variable(ref var) => code.push(Instruction::Position(var.index)),
+        Expression::EndVariable(ref var) => code.push(Instruction::FromEnd(var.offset)),
+        Expression::Length => code.push(Instruction::Length),
+        Expression::Predicate(ref predicate) => code.push(Instruction::Predicate(predicate.clone())),
+        Expression::Value(Value::Numerical(n)) => code.push(Instruction::Constant(n)),
+        Expression::Value(Value::Boolean(b)) => code.push(Instruction::Constant(b as i64)),
+        Expression::Not(ref exp) => {
+            emit(exp, code);
+            code.push(Instruction::Not);
+        },
+        Expression::BinaryExp(ref bin_exp) => {
+            emit(&bin_exp.l_value, code);
+            let r = constant(&bin_exp.r_value);
+            if r.is_none() {
+                emit(&bin_exp.r_value, code);
+            }
+            match bin_exp.operator {
+                Operator::Binary(ref op) => {
+                    let op = match *op {
+                        BinaryOperator::Add => Arithmetic::Add,
+                        BinaryOperator::Subtract => Arithmetic::Subtract,
+                        BinaryOperator::Multiply => Arithmetic::Multiply,
+                        BinaryOperator::Modulo => Arithmetic::Modulo,
+                    };
+                    code.push(match r {
+                        Some(r) => Instruction::ArithmeticWith(op, r),
+                        None => Instruction::Arithmetic(op),
+                    });
+                    if let Some(modulus) = bin_exp.modulus {
+                        code.push(Instruction::Wrap(modulus));
+                    }
+                },
+                Operator::Boolean(ref op) => {
+                    let cmp = match *op {
+                        BooleanOperator::Equal => Comparison::Equal,
+                        BooleanOperator::LessThan => Comparison::LessThan,
+                        BooleanOperator::GreaterThan => Comparison::GreaterThan,
+                    };
+                    code.push(match r {
+                        Some(r) => Instruction::CompareWith(cmp, r),
+                        None => Instruction::Compare(cmp),
+                    });
+                },
+            }
+        },
+    }
+}
+
+/// The value of an expression that doesn't read the combination, as
+/// long as working it out doesn't fail.
+fn constant(expression: &Expression) -> Option<i64> {
+    if !expression.positions()?.is_empty() {
+        return None;
+    }
+    match expression.evaluate(&[]).ok()? {
+        Value::Numerical(n) => Some(n),
+        Value::Boolean(b) => Some(b as i64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast::{convert_string_to_ast, parse_code, Context};
+    use alphabet::Alphabet;
+    use lock::Lock;
+    use space::{Space, CombinationGenerator};
+    use batch::BATCH_SIZE;
+
+    fn agree_on(rules: &[&str], lock: &Lock, alphabet: &Alphabet, total_vars: usize, combinations: &[Vec<u8>]) {
+        let context = Context { total_vars, alphabet, lock };
+        for text in rules.iter() {
+            let rule = convert_string_to_ast(text, &context).unwrap();
+            let program = Program::compile(&rule);
+            for combination in combinations.iter() {
+                assert_eq!(program.holds(combination), rule.holds(combination), "{} on {:?}", text, combination);
+            }
+
+            let max_length = combinations.iter().map(|c| c.len()).max().unwrap_or(0);
+            for chunk in combinations.chunks(BATCH_SIZE) {
+                let mut batch = Batch::new(max_length);
+                batch.set_len(chunk.len());
+                for (k, combination) in chunk.iter().enumerate() {
+                    for (p, value) in combination.iter().enumerate() {
+                        batch.columns[p][k] = *value;
+                    }
+                    batch.lengths[k] = combination.len() as u8;
+                }
+                let mut keep = vec![true; chunk.len()];
+                program.holds_batch(&batch, &mut keep);
+                for (combination, kept) in chunk.iter().zip(keep.iter()) {
+                    assert_eq!(*kept, rule.holds(combination), "{} on {:?} in a batch", text, combination);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn agrees_with_tree_on_codes() {
+        let rules = [
+            "a + b = 9", "a * b * c > 100", "a - b - c < 0", "c % 3 = 1", "a % b = 0",
+            "2 + 2 = a", "3 * 3 = 9", "1 = 2", "a + b * c - d = 7", "d[-1] = len", "e = 3", "d[-4] > d[2]",
+            "'7' = b", "len * 2 = 8",
+        ];
+        let combinations: Vec<Vec<u8>> = (2..=4).flat_map(|n| CombinationGenerator::new(vec![(0..10).collect(); n])).collect();
+        agree_on(&rules, &Lock::Code, &Alphabet::decimal(), 5, &combinations);
+    }
+
+    #[test]
+    fn agrees_with_tree_on_dials() {
+        let rules = ["a + 5 = 2", "a - b = 39", "a % 4 = c % 4", "a * 3 > 30"];
+        let combinations: Vec<Vec<u8>> = CombinationGenerator::new(vec![(0..40).collect(); 3]).collect();
+        agree_on(&rules, &Lock::Dial { size: 40, tolerance: 1 }, &Alphabet::dial(40).unwrap(), 3, &combinations);
+    }
+
+    #[test]
+    fn agrees_with_tree_on_predicates() {
+        let buttons = Alphabet::from_chars("12345").unwrap();
+        let rules = ["count = 2", "together(1, 4)", "!pressed(3)", "step(2) > step(5)"];
+        let combinations = Lock::push_button_combinations(5, true);
+        agree_on(&rules, &Lock::PushButton { ordered: true }, &buttons, 5, &combinations);
+
+        let dots = Alphabet::with_values(&[("1", 1), ("2", 2), ("3", 3), ("4", 4), ("5", 5), ("6", 6), ("7", 7), ("8", 8), ("9", 9)]).unwrap();
+        let rules = ["starts_at_corner", "turns = 2", "!crosses", "len = 5"];
+        let space = Space::table(&Lock::pattern_combinations());
+        let mut combination = Vec::new();
+        let combinations: Vec<Vec<u8>> = (0..space.size().unwrap()).step_by(97).map(|i| { space.decode(i, &mut combination); combination.clone() }).collect();
+        agree_on(&rules, &Lock::Pattern, &dots, 9, &combinations);
+    }
+
+    #[test]
+    fn inlines_constants() {
+        let rule = parse_code("a + 2 * 3 = 4 + 5", 4);
+        let program = Program::compile(&rule);
+        assert_eq!(program.code.len(), 3);
+        assert_eq!(program.depth, 1);
+        assert_eq!(program.evaluate(&[3]), Some(1));
+    }
+
+    #[test]
+    fn deep_programs() {
+        use ast::expression::BinaryExpression;
+        use ast::variable::Variable;
+
+        // a + (b + (c + ...)) needs a stack as deep as it is long.
+        let sum = (0..20).rev().fold(None, |sum: Option<Expression>, index| {
+            let var = Expression::Variable(Variable { index });
+            Some(match sum {
+                None => var,
+                Some(sum) => Expression::BinaryExp(BinaryExpression { l_value: Box::new(var), operator: Operator::Binary(BinaryOperator::Add), modulus: None, r_value: Box::new(sum) }),
+            })
+        }).unwrap();
+        let program = Program::compile(&sum);
+        assert_eq!(program.depth, 20);
+        assert_eq!(program.evaluate(&[1; 20]), Some(20));
+        assert_eq!(program.evaluate(&[1; 19]), None);
+    }
+}
@@ -1,15 +1,17 @@
+use error::EvalError;
+
 #[derive(Debug, PartialEq, PartialOrd, Eq, Clone)]
 pub enum Value {
-    Numerical(u8),
+    Numerical(i64),
     Boolean(bool),
 }
 
 pub trait Evaluable {
-    fn evaluate(&self, arr: &Vec<u8>) -> Result<Value, &'static str>;
+    fn evaluate(&self, arr: &[u8]) -> Result<Value, EvalError>;
 }
 
 impl Evaluable for Value {
-    fn evaluate(&self, _arr: &Vec<u8>) -> Result<Value, &'static str> {
+    fn evaluate(&self, _arr: &[u8]) -> Result<Value, EvalError> {
         Ok(self.clone())
     }
 }
@@ -1,11 +1,19 @@
 use ast::operator::{Operator, BinaryOperator, BooleanOperator};
 use ast::evaluable::{Value, Evaluable};
-use ast::variable::Variable;
+use ast::variable::{Variable, EndVariable};
+use ast::predicate::Predicate;
+use alphabet::Alphabet;
+use error::EvalError;
+use position_name;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Expression {
     BinaryExp(BinaryExpression),
     Variable(Variable),
+    EndVariable(EndVariable),
+    Length,
+    Predicate(Predicate),
+    Not(Box<Expression>),
     Value(Value),
 }
 
@@ -13,47 +21,110 @@ pub enum Expression {
 pub struct BinaryExpression {
     pub l_value: Box<Expression>,
     pub operator: Operator,
+    /// When set, the result of arithmetic wraps around modulo this
+    /// value, like the numbers on a dial.
+    pub modulus: Option<i64>,
     pub r_value: Box<Expression>,
 }
 
+impl Expression {
+    /// The rule that `predicate`, which counts something about the
+    /// combination, comes to `count`.
+    pub fn counts(predicate: Predicate, count: usize) -> Expression {
+        Expression::BinaryExp(BinaryExpression {
+            l_value: Box::new(Expression::Predicate(predicate)),
+            operator: Operator::Boolean(BooleanOperator::Equal),
+            modulus: None,
+            r_value: Box::new(Expression::Value(Value::Numerical(count as i64))),
+        })
+    }
+
+    /// Whether the expression evaluates to true. One that can't be
+    /// evaluated at all doesn't hold.
+    pub fn holds(&self, arr: &[u8]) -> bool {
+        matches!(self.evaluate(arr), Ok(Value::Boolean(true)))
+    }
+
+    /// The positions the expression reads, in no particular order, or
+    /// `None` if it depends on the combination as a whole, like `len`,
+    /// `d[-1]` and predicates do.
+    pub fn positions(&self) -> Option<Vec<usize>> {
+        match *self {
+            Expression::Variable(ref var) => Some(vec![var.index]),
+            Expression::BinaryExp(ref bin_exp) => {
+                let mut positions = bin_exp.l_value.positions()?;
+                positions.extend(bin_exp.r_value.positions()?);
+                Some(positions)
+            },
+            Expression::Not(ref exp) => exp.positions(),
+            Expression::Value(_) => Some(Vec::new()),
+            Expression::EndVariable(_) | Expression::Length | Expression::Predicate(_) => None,
+        }
+    }
+
+    /// Writes the expression out the way it would be written in a rule.
+    /// Rules have no parentheses, so neither does this, and parsing the
+    /// result gives back the same expression.
+    pub fn render(&self, alphabet: &Alphabet) -> String {
+        match *self {
+            Expression::BinaryExp(ref bin_exp) => format!("{} {} {}", bin_exp.l_value.render(alphabet), bin_exp.operator.symbol(), bin_exp.r_value.render(alphabet)),
+            Expression::Variable(ref var) => position_name(var.index),
+            Expression::EndVariable(ref var) => format!("d[-{}]", var.offset),
+            Expression::Length => "len".to_owned(),
+            Expression::Predicate(ref predicate) => predicate.render(alphabet),
+            Expression::Not(ref exp) => format!("!{}", exp.render(alphabet)),
+            Expression::Value(Value::Numerical(n)) => n.to_string(),
+            Expression::Value(Value::Boolean(b)) => b.to_string(),
+        }
+    }
+}
+
 impl Evaluable for Expression {
-    fn evaluate(&self, arr: &Vec<u8>) -> Result<Value, &'static str> {
-        match self {
-            &Expression::Variable(ref var) => var.evaluate(&arr),
-            &Expression::BinaryExp(ref bin_exp) => bin_exp.evaluate(&arr),
-            &Expression::Value(ref val) => val.evaluate(&arr),
+    fn evaluate(&self, arr: &[u8]) -> Result<Value, EvalError> {
+        match *self {
+            Expression::Variable(ref var) => var.evaluate(arr),
+            Expression::EndVariable(ref var) => var.evaluate(arr),
+            Expression::BinaryExp(ref bin_exp) => bin_exp.evaluate(arr),
+            Expression::Length => Ok(Value::Numerical(arr.len() as i64)),
+            Expression::Predicate(ref predicate) => predicate.evaluate(arr),
+            Expression::Not(ref exp) => match exp.evaluate(arr)? {
+                Value::Boolean(b) => Ok(Value::Boolean(!b)),
+                _ => Err(EvalError::NotABoolean),
+            },
+            Expression::Value(ref val) => val.evaluate(arr),
         }
     }
 }
 
 impl Evaluable for BinaryExpression {
-    fn evaluate(&self, arr: &Vec<u8>) -> Result<Value, &'static str> {
-        if let Operator::Binary(ref op) = self.operator {
-            let (l, r) = match (self.l_value.evaluate(&arr)?, self.r_value.evaluate(&arr)?) {
-                (Value::Numerical(l_val), Value::Numerical(r_val)) => (Ok(l_val), Ok(r_val)),
-                _ => (Err("Not a number"), Err("Not a number")),
-            };
-
-            match (l, op, r) {
-                (Ok(l_val), &BinaryOperator::Multiply, Ok(r_val)) => Ok(Value::Numerical(l_val * r_val)),
-                (Ok(l_val), &BinaryOperator::Add, Ok(r_val)) => Ok(Value::Numerical(l_val + r_val)),
-                (Ok(l_val), &BinaryOperator::Subtract, Ok(r_val)) => Ok(Value::Numerical(l_val - r_val)),
-                _ => Err("Not a number"),
-            }
-        } else if let Operator::Boolean(ref op) = self.operator {
-            let (l, r) = match (self.l_value.evaluate(&arr)?, self.r_value.evaluate(&arr)?) {
-                (Value::Numerical(l_val), Value::Numerical(r_val)) => (Ok(l_val), Ok(r_val)),
-                _ => (Err("Not a bool"), Err("Not a bool")),
-            };
-
-            match (l, op, r) {
-                (Ok(l_val), &BooleanOperator::Equal, Ok(r_val)) => Ok(Value::Boolean(l_val == r_val)),
-                (Ok(l_val), &BooleanOperator::GreaterThan, Ok(r_val)) => Ok(Value::Boolean(l_val > r_val)),
-                (Ok(l_val), &BooleanOperator::LessThan, Ok(r_val)) => Ok(Value::Boolean(l_val < r_val)),
-                _ => Err("Not a bool"),
-            }
-        } else {
-            Err("Something went wrong...")
+    fn evaluate(&self, arr: &[u8]) -> Result<Value, EvalError> {
+        let (l_val, r_val) = match (self.l_value.evaluate(arr)?, self.r_value.evaluate(arr)?) {
+            (Value::Numerical(l_val), Value::Numerical(r_val)) => (l_val, r_val),
+            _ => return Err(EvalError::NotANumber),
+        };
+        match self.operator {
+            Operator::Binary(ref op) => {
+                let result = match *op {
+                    BinaryOperator::Multiply => l_val.checked_mul(r_val).ok_or(EvalError::Overflow)?,
+                    BinaryOperator::Add => l_val.checked_add(r_val).ok_or(EvalError::Overflow)?,
+                    BinaryOperator::Subtract => l_val.checked_sub(r_val).ok_or(EvalError::Overflow)?,
+                    BinaryOperator::Modulo => {
+                        if r_val == 0 {
+                            return Err(EvalError::ModuloByZero);
+                        }
+                        l_val.rem_euclid(r_val)
+                    },
+                };
+                match self.modulus {
+                    Some(modulus) => Ok(Value::Numerical(result.rem_euclid(modulus))),
+                    None => Ok(Value::Numerical(result)),
+                }
+            },
+            Operator::Boolean(ref op) => Ok(Value::Boolean(match *op {
+                BooleanOperator::Equal => l_val == r_val,
+                BooleanOperator::GreaterThan => l_val > r_val,
+                BooleanOperator::LessThan => l_val < r_val,
+            })),
         }
     }
 }
@@ -64,10 +135,62 @@ mod tests {
 
     #[test]
     fn test_evaluation() {
-        let t = Expression::BinaryExp(BinaryExpression {l_value: Box::new(Expression::Variable(Variable {name: 'a'})), operator: Operator::Boolean(BooleanOperator::Equal), r_value: Box::new(Expression::Value(Value::Numerical(3)))});
+        let t = Expression::BinaryExp(BinaryExpression {l_value: Box::new(Expression::Variable(Variable {index: 0})), operator: Operator::Boolean(BooleanOperator::Equal), modulus: None, r_value: Box::new(Expression::Value(Value::Numerical(3)))});
 
-        assert!(t.evaluate(&vec![1,2,3,4]) == Ok(Value::Boolean(false)));
-        assert!(t.evaluate(&vec![3,2,3,4]) == Ok(Value::Boolean(true)));
+        assert!(t.evaluate(&[1,2,3,4]) == Ok(Value::Boolean(false)));
+        assert!(t.evaluate(&[3,2,3,4]) == Ok(Value::Boolean(true)));
     }
-}
 
+    #[test]
+    fn test_modular_evaluation() {
+        let sum = Expression::BinaryExp(BinaryExpression {l_value: Box::new(Expression::Variable(Variable {index: 0})), operator: Operator::Binary(BinaryOperator::Add), modulus: Some(40), r_value: Box::new(Expression::Value(Value::Numerical(5)))});
+        let difference = Expression::BinaryExp(BinaryExpression {l_value: Box::new(Expression::Variable(Variable {index: 0})), operator: Operator::Binary(BinaryOperator::Subtract), modulus: Some(40), r_value: Box::new(Expression::Value(Value::Numerical(5)))});
+
+        assert_eq!(sum.evaluate(&[37]), Ok(Value::Numerical(2)));
+        assert_eq!(difference.evaluate(&[2]), Ok(Value::Numerical(37)));
+    }
+
+    #[test]
+    fn test_negative_evaluation() {
+        let difference = Expression::BinaryExp(BinaryExpression {l_value: Box::new(Expression::Variable(Variable {index: 0})), operator: Operator::Binary(BinaryOperator::Subtract), modulus: None, r_value: Box::new(Expression::Variable(Variable {index: 1}))});
+        let remainder = Expression::BinaryExp(BinaryExpression {l_value: Box::new(Expression::Variable(Variable {index: 0})), operator: Operator::Binary(BinaryOperator::Modulo), modulus: None, r_value: Box::new(Expression::Variable(Variable {index: 1}))});
+
+        assert_eq!(difference.evaluate(&[2, 5]), Ok(Value::Numerical(-3)));
+        assert_eq!(remainder.evaluate(&[9, 4]), Ok(Value::Numerical(1)));
+        assert_eq!(remainder.evaluate(&[9, 0]), Err(EvalError::ModuloByZero));
+    }
+
+    #[test]
+    fn test_positions() {
+        let sum = Expression::BinaryExp(BinaryExpression {l_value: Box::new(Expression::Variable(Variable {index: 2})), operator: Operator::Binary(BinaryOperator::Add), modulus: None, r_value: Box::new(Expression::Variable(Variable {index: 0}))});
+        let to_end = Expression::BinaryExp(BinaryExpression {l_value: Box::new(sum.clone()), operator: Operator::Boolean(BooleanOperator::Equal), modulus: None, r_value: Box::new(Expression::EndVariable(EndVariable {offset: 1}))});
+
+        assert_eq!(sum.positions(), Some(vec![2, 0]));
+        assert_eq!(Expression::Not(Box::new(sum)).positions(), Some(vec![2, 0]));
+        assert_eq!(Expression::Value(Value::Numerical(4)).positions(), Some(vec![]));
+        assert_eq!(to_end.positions(), None);
+    }
+
+    #[test]
+    fn test_render() {
+        use ast::{convert_string_to_ast, Context};
+        use ast::bytecode::Program;
+        use lock::Lock;
+
+        let alphabet = Alphabet::hexadecimal();
+        let context = Context { total_vars: 4, alphabet: &alphabet, lock: &Lock::Code };
+        let rules = [("a+b*c=7", "a + b * c = 7"), ("d[-1] > 'f'", "d[-1] > 15"), ("d[2] % 3 < len - a", "b % 3 < len - a"), ("a - b < c", "a - b < c"), ("a - b % 3 > c", "a - b % 3 > c")];
+        for (rule, rendered) in rules.iter() {
+            let expression = convert_string_to_ast(rule, &context).unwrap();
+            assert_eq!(expression.render(&alphabet), *rendered);
+            // Operators of a kind compare equal, so the round trip is
+            // checked on what it renders and compiles to instead.
+            let reparsed = convert_string_to_ast(rendered, &context).unwrap();
+            assert_eq!(reparsed.render(&alphabet), *rendered);
+            assert_eq!(format!("{:?}", Program::compile(&reparsed)), format!("{:?}", Program::compile(&expression)));
+        }
+        let buttons = Alphabet::from_chars("ABC").unwrap();
+        let context = Context { total_vars: 3, alphabet: &buttons, lock: &Lock::PushButton { ordered: true } };
+        assert_eq!(convert_string_to_ast("!together(A, C)", &context).unwrap().render(&buttons), "!together(A, C)");
+    }
+}
@@ -2,48 +2,109 @@ pub mod evaluable;
 pub mod variable;
 pub mod operator;
 pub mod expression;
+pub mod predicate;
+pub mod bytecode;
 
 use self::evaluable::Value;
-use self::variable::Variable;
+use self::variable::{Variable, EndVariable};
 use self::expression::{Expression, BinaryExpression};
 use self::operator::Operator;
-use self::operator::BinaryOperator::{Add, Subtract, Multiply};
+use alphabet::Alphabet;
+use lock::Lock;
+use error::RuleError;
+#[cfg(test)]
+use std::sync::OnceLock;
+use self::operator::BinaryOperator::{Add, Subtract, Multiply, Modulo};
 use self::operator::BooleanOperator::{Equal, GreaterThan, LessThan};
 
-pub fn convert_string_to_ast(input: &str, total_vars: u8) -> Result<Box<Expression>, String> {
-    let mut exp_vec: Vec<Box<Expression>> = Vec::new();
+/// Everything about the passcode a rule is parsed against: how many
+/// positions it has, the symbols quoted literals are looked up in, and
+/// the kind of lock, which decides the predicates that can be used.
+pub struct Context<'a> {
+    pub total_vars: usize,
+    pub alphabet: &'a Alphabet,
+    pub lock: &'a Lock,
+}
+
+pub fn convert_string_to_ast(input: &str, context: &Context) -> Result<Box<Expression>, RuleError> {
+    let mut exp_vec: Vec<Expression> = Vec::new();
     let mut op_vec: Vec<Operator> = Vec::new();
-    let last_char = (total_vars + 96u8) as char;
-    for c in input.to_lowercase().chars() {
-        match c {
-            v @ 'a'...'z' => {
-                if v > last_char {
-                    return Err(format!("Invalid variable added. You may only use 'a' through '{}'. You used: '{}'", last_char, v));
+    let mut negate = false;
+    let total_vars = context.total_vars;
+    // The last position that has a letter of its own.
+    let last_char = (b'a' + total_vars.clamp(1, 26) as u8 - 1) as char;
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        let exp = match c.to_ascii_lowercase() {
+            v @ 'a'..='z' => {
+                // Letters run together make one name, so `ab` is never
+                // the positions `a` and `b`.
+                let mut name = v.to_string();
+                while let Some(&next) = chars.peek() {
+                    if !next.is_ascii_alphabetic() && next != '_' {
+                        break;
+                    }
+                    name.push(next.to_ascii_lowercase());
+                    chars.next();
+                }
+                let args = if chars.peek() == Some(&'(') {
+                    chars.next();
+                    Some(parse_arguments(chars.by_ref())?)
+                } else {
+                    None
+                };
+                match (name.as_str(), args) {
+                    ("len", None) => Expression::Length,
+                    ("d", None) if chars.peek() == Some(&'[') => {
+                        chars.next();
+                        parse_index(&mut chars, total_vars)?
+                    },
+                    (_, None) if name.len() == 1 => {
+                        let index = (v as u8 - b'a') as usize;
+                        if index >= total_vars {
+                            return Err(RuleError::InvalidVariable { last: last_char, found: v });
+                        }
+                        Expression::Variable(Variable { index })
+                    },
+                    (_, args) => {
+                        let args = args.unwrap_or_default();
+                        match predicate::resolve(&name, &args, context)? {
+                            Some(predicate) => Expression::Predicate(predicate),
+                            None => return Err(RuleError::UnknownName(name)),
+                        }
+                    },
                 }
-                exp_vec.push(Box::new(Expression::Variable(Variable { name: v })));
+            },
+            '*' => {
+                op_vec.push(Operator::Binary(Multiply));
                 continue;
             },
-            '*' => op_vec.push(Operator::Binary(Multiply)),
-            v @ '0'...'9' => {
-                let number: u8 = match v {
-                    '0' => 0,
-                    '1' => 1,
-                    '2' => 2,
-                    '3' => 3,
-                    '4' => 4,
-                    '5' => 5,
-                    '6' => 6,
-                    '7' => 7,
-                    '8' => 8,
-                    '9' => 9,
-                    _ => 0,
-                };
-                exp_vec.push(Box::new(Expression::Value(Value::Numerical(number))));
+            v @ '0'..='9' => {
+                let mut number = v.to_digit(10).unwrap() as i64;
+                while let Some(digit) = chars.peek().and_then(|d| d.to_digit(10)) {
+                    number = match number.checked_mul(10).and_then(|n| n.checked_add(digit as i64)) {
+                        Some(n) => n,
+                        None => return Err(RuleError::NumberTooLarge),
+                    };
+                    chars.next();
+                }
+                Expression::Value(Value::Numerical(number))
+            },
+            quote @ '\'' | quote @ '"' => {
+                let symbol = parse_quoted(&mut chars, quote)?;
+                match context.alphabet.symbol_value(&symbol) {
+                    Some(value) => Expression::Value(Value::Numerical(value as i64)),
+                    None => return Err(RuleError::UnknownSymbol(symbol)),
+                }
+            },
+            '!' => {
+                negate = !negate;
                 continue;
             },
-            o @ '*'...'>' => {
+            o @ '*'..='>' | o @ '%' => {
                 let op = match o {
                     '*' => Operator::Binary(Multiply),
+                    '%' => Operator::Binary(Modulo),
                     '-' => Operator::Binary(Subtract),
                     '+' => Operator::Binary(Add),
                     '=' => Operator::Boolean(Equal),
@@ -51,11 +112,19 @@ pub fn convert_string_to_ast(input: &str, total_vars: u8) -> Result<Box<Expressi
                     '>' => Operator::Boolean(GreaterThan),
                     _ => continue,
                 };
-                if !handle_ops(&mut exp_vec, &mut op_vec, op) {
-                    return Err(format!("Malformed input string"));
+                if negate || !handle_ops(&mut exp_vec, &mut op_vec, op) {
+                    return Err(RuleError::Malformed);
                 }
+                continue;
             },
             _ => continue,
+        };
+
+        if negate {
+            exp_vec.push(Expression::Not(Box::new(exp)));
+            negate = false;
+        } else {
+            exp_vec.push(exp);
         }
     }
 
@@ -64,27 +133,107 @@ pub fn convert_string_to_ast(input: &str, total_vars: u8) -> Result<Box<Expressi
         let l_value = exp_vec.pop();
         match (l_value, r_value) {
             (Some(l), Some(r)) => {
-                exp_vec.push(Box::new(Expression::BinaryExp(BinaryExpression { l_value: l, operator: op, r_value: r })));
+                exp_vec.push(Expression::BinaryExp(BinaryExpression { l_value: Box::new(l), operator: op, modulus: None, r_value: Box::new(r) }));
             },
-            _ => return Err(format!("Malformed input string")),
+            _ => return Err(RuleError::Malformed),
         }
     }
 
-    if let Some(final_exp) = exp_vec.pop() {
-        validate_ast(final_exp)
+    if let Some(mut final_exp) = exp_vec.pop() {
+        if let Lock::Dial { size, .. } = *context.lock {
+            wrap_arithmetic(&mut final_exp, size as i64);
+        }
+        validate_ast(Box::new(final_exp))
     } else {
-        Err(format!("Something went wrong I guess"))
+        Err(RuleError::Empty)
+    }
+}
+
+/// The context of a code of `total_vars` decimal digits, which is what
+/// most tests check rules against.
+#[cfg(test)]
+pub fn code_context(total_vars: usize) -> Context<'static> {
+    static DECIMAL: OnceLock<Alphabet> = OnceLock::new();
+    Context { total_vars, alphabet: DECIMAL.get_or_init(Alphabet::decimal), lock: &Lock::Code }
+}
+
+/// Parses a rule against `code_context`.
+#[cfg(test)]
+pub fn parse_code(rule: &str, total_vars: usize) -> Expression {
+    *convert_string_to_ast(rule, &code_context(total_vars)).unwrap()
+}
+
+/// Reads a quoted symbol up to its closing quote, the opening quote
+/// having already been consumed.
+fn parse_quoted<I: Iterator<Item = char>>(chars: &mut I, quote: char) -> Result<String, RuleError> {
+    let mut symbol = String::new();
+    for s in chars {
+        if s == quote {
+            return Ok(symbol);
+        }
+        symbol.push(s);
     }
+    Err(RuleError::UnterminatedSymbol(format!("{}{}", quote, symbol)))
 }
 
-fn handle_ops(exp_vec: &mut Vec<Box<Expression>>, op_vec: &mut Vec<Operator>, new_op: Operator) -> bool {
+/// Parses the comma separated arguments of a predicate call such as
+/// `together(1, 4)`, the opening parenthesis having already been
+/// consumed. Arguments are symbols and may be quoted.
+fn parse_arguments<I: Iterator<Item = char>>(chars: &mut I) -> Result<Vec<String>, RuleError> {
+    let mut args: Vec<String> = vec![String::new()];
+    loop {
+        match chars.next() {
+            Some(')') => break,
+            Some(',') => args.push(String::new()),
+            Some(quote @ '\'') | Some(quote @ '"') => {
+                let symbol = parse_quoted(chars, quote)?;
+                args.last_mut().unwrap().push_str(&symbol);
+            },
+            Some(c) if c.is_whitespace() => continue,
+            Some(c) => args.last_mut().unwrap().push(c),
+            None => return Err(RuleError::UnterminatedCall),
+        }
+    }
+    if args.len() == 1 && args[0].is_empty() {
+        return Ok(Vec::new());
+    }
+    if args.iter().any(|a| a.is_empty()) {
+        return Err(RuleError::EmptyArgument);
+    }
+    Ok(args)
+}
+
+/// Parses the inside of `d[...]`, which is either a position counted
+/// from the start (`d[1]` is the same as `a`) or from the end
+/// (`d[-1]` is the last position).
+fn parse_index<I: Iterator<Item = char>>(chars: &mut ::std::iter::Peekable<I>, total_vars: usize) -> Result<Expression, RuleError> {
+    let mut index = String::new();
+    loop {
+        match chars.next() {
+            Some(']') => break,
+            Some(c) if c.is_whitespace() => continue,
+            Some(c) if c.is_ascii_digit() || (c == '-' && index.is_empty()) => index.push(c),
+            Some(c) => return Err(RuleError::UnexpectedInIndex(c)),
+            None => return Err(RuleError::UnterminatedIndex),
+        }
+    }
+    let total_vars = total_vars as i64;
+    match index.parse::<i64>() {
+        Ok(i) if i >= 1 && i <= total_vars => Ok(Expression::Variable(Variable { index: (i - 1) as usize })),
+        Ok(i) if i <= -1 && -i <= total_vars => Ok(Expression::EndVariable(EndVariable { offset: (-i) as usize })),
+        Ok(i) => Err(RuleError::IndexOutOfRange { index: i, positions: total_vars }),
+        Err(_) => Err(RuleError::InvalidIndex(index)),
+    }
+}
+
+fn handle_ops(exp_vec: &mut Vec<Expression>, op_vec: &mut Vec<Operator>, new_op: Operator) -> bool {
     while let Some(x) = op_vec.pop() {
         if new_op <= x {
             let r_value = exp_vec.pop();
             let l_value = exp_vec.pop();
             match (l_value, r_value) {
                 (Some(l), Some(r)) => {
-                    exp_vec.push(Box::new(Expression::BinaryExp(BinaryExpression { l_value: l, operator: x, r_value: r })));
+                    exp_vec.push(Expression::BinaryExp(BinaryExpression { l_value: Box::new(l), operator: x, modulus: None, r_value: Box::new(r) }));
                 },
                 _ => return false,
             }
@@ -97,30 +246,74 @@ fn handle_ops(exp_vec: &mut Vec<Box<Expression>>, op_vec: &mut Vec<Operator>, ne
     true
 }
 
-fn validate_ast(ast: Box<Expression>) -> Result<Box<Expression>, String> {
-    match *ast {
-        Expression::BinaryExp(BinaryExpression {l_value: _, operator: Operator::Boolean(_), r_value: _}) => Ok(ast),
-        _ => Err(format!("Malformed AST, a boolean operator ('=', '<', '>') is required")),
+/// Makes every addition, subtraction and multiplication in the rule
+/// wrap around modulo `modulus`.
+fn wrap_arithmetic(exp: &mut Expression, modulus: i64) {
+    match *exp {
+        Expression::BinaryExp(ref mut bin_exp) => {
+            if let Operator::Binary(_) = bin_exp.operator {
+                bin_exp.modulus = Some(modulus);
+            }
+            wrap_arithmetic(&mut bin_exp.l_value, modulus);
+            wrap_arithmetic(&mut bin_exp.r_value, modulus);
+        },
+        Expression::Not(ref mut inner) => wrap_arithmetic(inner, modulus),
+        _ => {},
+    }
+}
+
+fn validate_ast(ast: Box<Expression>) -> Result<Box<Expression>, RuleError> {
+    check_boolean(&ast)?;
+    Ok(ast)
+}
+
+fn check_boolean(exp: &Expression) -> Result<(), RuleError> {
+    match *exp {
+        Expression::BinaryExp(BinaryExpression {ref l_value, operator: Operator::Boolean(_), ref r_value, ..}) => {
+            if !is_numerical(l_value) || !is_numerical(r_value) {
+                return Err(RuleError::NotNumbers);
+            }
+            Ok(())
+        },
+        Expression::Predicate(ref predicate) if predicate.is_boolean() => Ok(()),
+        Expression::Not(ref inner) => check_boolean(inner),
+        _ => Err(RuleError::NotBoolean),
+    }
+}
+
+fn is_numerical(exp: &Expression) -> bool {
+    match *exp {
+        Expression::BinaryExp(BinaryExpression {ref l_value, operator: Operator::Binary(_), ref r_value, ..}) => {
+            is_numerical(l_value) && is_numerical(r_value)
+        },
+        Expression::Predicate(ref predicate) => !predicate.is_boolean(),
+        Expression::BinaryExp(_) | Expression::Not(_) | Expression::Value(Value::Boolean(_)) => false,
+        Expression::Variable(_) | Expression::EndVariable(_) | Expression::Length | Expression::Value(Value::Numerical(_)) => true,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use self::predicate::Predicate;
+
+    fn convert(input: &str) -> Result<Box<Expression>, RuleError> {
+        convert_string_to_ast(input, &code_context(4))
+    }
 
     #[test]
     fn handle_equal_ops() {
-        let mut exp_vec: Vec<Box<Expression>> = Vec::new();
+        let mut exp_vec: Vec<Expression> = Vec::new();
         let mut op_vec: Vec<Operator> = Vec::new();
 
-        let l_var = Expression::Variable(Variable {name: 'a'});
-        let r_var = Expression::Variable(Variable {name: 'b'});
+        let l_var = Expression::Variable(Variable {index: 0});
+        let r_var = Expression::Variable(Variable {index: 1});
 
-        exp_vec.push(Box::new(l_var.clone()));
-        exp_vec.push(Box::new(r_var.clone()));
+        exp_vec.push(l_var.clone());
+        exp_vec.push(r_var.clone());
         op_vec.push(Operator::Binary(Add));
 
-        let expected_result = Some(Box::new(Expression::BinaryExp(BinaryExpression {l_value: Box::new(l_var.clone()), operator: Operator::Binary(Add), r_value: Box::new(r_var.clone())})));
+        let expected_result = Some(Expression::BinaryExp(BinaryExpression {l_value: Box::new(l_var.clone()), operator: Operator::Binary(Add), modulus: None, r_value: Box::new(r_var.clone())}));
 
         let _ = handle_ops(&mut exp_vec, &mut op_vec, Operator::Binary(Subtract));
         assert_eq!(exp_vec.pop(), expected_result);
@@ -129,17 +322,17 @@ mod tests {
 
     #[test]
     fn handle_greater_ops() {
-        let mut exp_vec: Vec<Box<Expression>> = Vec::new();
+        let mut exp_vec: Vec<Expression> = Vec::new();
         let mut op_vec: Vec<Operator> = Vec::new();
 
-        let l_var = Expression::Variable(Variable {name: 'a'});
-        let r_var = Expression::Variable(Variable {name: 'b'});
+        let l_var = Expression::Variable(Variable {index: 0});
+        let r_var = Expression::Variable(Variable {index: 1});
 
-        exp_vec.push(Box::new(l_var.clone()));
-        exp_vec.push(Box::new(r_var.clone()));
+        exp_vec.push(l_var.clone());
+        exp_vec.push(r_var.clone());
         op_vec.push(Operator::Binary(Add));
         
-        let expected_result = Some(Box::new(r_var));
+        let expected_result = Some(r_var);
         let _ = handle_ops(&mut exp_vec, &mut op_vec, Operator::Binary(Multiply));
         assert_eq!(exp_vec.pop(), expected_result);
         assert_eq!(op_vec.pop(), Some(Operator::Binary(Multiply)));
@@ -147,17 +340,17 @@ mod tests {
 
     #[test]
     fn handle_lesser_ops() {
-        let mut exp_vec: Vec<Box<Expression>> = Vec::new();
+        let mut exp_vec: Vec<Expression> = Vec::new();
         let mut op_vec: Vec<Operator> = Vec::new();
 
-        let l_var = Expression::Variable(Variable {name: 'a'});
-        let r_var = Expression::Variable(Variable {name: 'b'});
+        let l_var = Expression::Variable(Variable {index: 0});
+        let r_var = Expression::Variable(Variable {index: 1});
 
-        exp_vec.push(Box::new(l_var.clone()));
-        exp_vec.push(Box::new(r_var.clone()));
+        exp_vec.push(l_var.clone());
+        exp_vec.push(r_var.clone());
         op_vec.push(Operator::Binary(Multiply));
         
-        let expected_result = Some(Box::new(Expression::BinaryExp(BinaryExpression {l_value: Box::new(l_var.clone()), operator: Operator::Binary(Multiply), r_value: Box::new(r_var.clone())})));
+        let expected_result = Some(Expression::BinaryExp(BinaryExpression {l_value: Box::new(l_var.clone()), operator: Operator::Binary(Multiply), modulus: None, r_value: Box::new(r_var.clone())}));
         let _ = handle_ops(&mut exp_vec, &mut op_vec, Operator::Boolean(Equal));
         assert_eq!(exp_vec.pop(), expected_result);
         assert_eq!(op_vec.pop(), Some(Operator::Boolean(Equal)));
@@ -165,20 +358,20 @@ mod tests {
 
     #[test]
     fn pop_multiple_ops() {
-        let mut exp_vec: Vec<Box<Expression>> = Vec::new();
+        let mut exp_vec: Vec<Expression> = Vec::new();
         let mut op_vec: Vec<Operator> = Vec::new();
 
-        let var1 = Expression::Variable(Variable {name: 'a'});
-        let var2 = Expression::Variable(Variable {name: 'b'});
-        let var3 = Expression::Variable(Variable {name: 'c'});
+        let var1 = Expression::Variable(Variable {index: 0});
+        let var2 = Expression::Variable(Variable {index: 1});
+        let var3 = Expression::Variable(Variable {index: 2});
 
-        exp_vec.push(Box::new(var1.clone()));
-        exp_vec.push(Box::new(var2.clone()));
-        exp_vec.push(Box::new(var3.clone()));
+        exp_vec.push(var1.clone());
+        exp_vec.push(var2.clone());
+        exp_vec.push(var3.clone());
         op_vec.push(Operator::Binary(Add));
         op_vec.push(Operator::Binary(Multiply));
         
-        let expected_result = Some(Box::new(Expression::BinaryExp(BinaryExpression {l_value: Box::new(var1.clone()), operator: Operator::Binary(Add), r_value: Box::new(Expression::BinaryExp(BinaryExpression {l_value: Box::new(var2.clone()), operator: Operator::Binary(Multiply), r_value: Box::new(var3.clone())}))})));
+        let expected_result = Some(Expression::BinaryExp(BinaryExpression {l_value: Box::new(var1.clone()), operator: Operator::Binary(Add), modulus: None, r_value: Box::new(Expression::BinaryExp(BinaryExpression {l_value: Box::new(var2.clone()), operator: Operator::Binary(Multiply), modulus: None, r_value: Box::new(var3.clone())}))}));
         let _ = handle_ops(&mut exp_vec, &mut op_vec, Operator::Boolean(Equal));
         assert_eq!(exp_vec.pop(), expected_result);
         assert_eq!(op_vec.pop(), Some(Operator::Boolean(Equal)));
@@ -188,29 +381,103 @@ mod tests {
     fn convert_boolean() {
         let input = "a = b";
 
-        let var1 = Expression::Variable(Variable {name: 'a'});
-        let var2 = Expression::Variable(Variable {name: 'b'});
-        let expected_result = Ok(Box::new(Expression::BinaryExp(BinaryExpression {l_value: Box::new(var1), operator: Operator::Boolean(Equal), r_value: Box::new(var2)})));
-        assert_eq!(convert_string_to_ast(input, 4), expected_result);
+        let var1 = Expression::Variable(Variable {index: 0});
+        let var2 = Expression::Variable(Variable {index: 1});
+        let expected_result = Ok(Box::new(Expression::BinaryExp(BinaryExpression {l_value: Box::new(var1), operator: Operator::Boolean(Equal), modulus: None, r_value: Box::new(var2)})));
+        assert_eq!(convert(input), expected_result);
     }
 
     #[test]
     fn convert_binary() {
         let input = "a + b";
 
-        let expected_result = Err(format!("Malformed AST, a boolean operator ('=', '<', '>') is required"));
-        assert_eq!(convert_string_to_ast(input, 4), expected_result);
+        let expected_result = Err(RuleError::NotBoolean);
+        assert_eq!(convert(input), expected_result);
     }
 
     #[test]
     fn convert_compound() {
         let input = "a * b = c";
 
-        let var1 = Expression::Variable(Variable {name: 'a'});
-        let var2 = Expression::Variable(Variable {name: 'b'});
-        let var3 = Expression::Variable(Variable {name: 'c'});
-        let expected_result = Ok(Box::new(Expression::BinaryExp(BinaryExpression {l_value: Box::new(Expression::BinaryExp(BinaryExpression {l_value: Box::new(var1), operator: Operator::Binary(Multiply), r_value: Box::new(var2)})), operator: Operator::Boolean(Equal), r_value: Box::new(var3)})));
-        assert_eq!(convert_string_to_ast(input, 4), expected_result);
+        let var1 = Expression::Variable(Variable {index: 0});
+        let var2 = Expression::Variable(Variable {index: 1});
+        let var3 = Expression::Variable(Variable {index: 2});
+        let expected_result = Ok(Box::new(Expression::BinaryExp(BinaryExpression {l_value: Box::new(Expression::BinaryExp(BinaryExpression {l_value: Box::new(var1), operator: Operator::Binary(Multiply), modulus: None, r_value: Box::new(var2)})), operator: Operator::Boolean(Equal), modulus: None, r_value: Box::new(var3)})));
+        assert_eq!(convert(input), expected_result);
+    }
+
+    #[test]
+    fn convert_multi_digit_number() {
+        let input = "a = 12";
+
+        let var1 = Expression::Variable(Variable {index: 0});
+        let expected_result = Ok(Box::new(Expression::BinaryExp(BinaryExpression {l_value: Box::new(var1), operator: Operator::Boolean(Equal), modulus: None, r_value: Box::new(Expression::Value(Value::Numerical(12)))})));
+        assert_eq!(convert(input), expected_result);
+        assert!(convert("a = 99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn convert_symbol_literal() {
+        let input = "a = 'F'";
+        let alphabet = Alphabet::from_chars("ABCDEF").unwrap();
+
+        let var1 = Expression::Variable(Variable {index: 0});
+        let expected_result = Ok(Box::new(Expression::BinaryExp(BinaryExpression {l_value: Box::new(var1), operator: Operator::Boolean(Equal), modulus: None, r_value: Box::new(Expression::Value(Value::Numerical(5)))})));
+        let context = Context { total_vars: 4, alphabet: &alphabet, lock: &Lock::Code };
+        assert_eq!(convert_string_to_ast(input, &context), expected_result);
+        assert_eq!(convert_string_to_ast("a = 'G'", &context), Err(RuleError::UnknownSymbol("G".to_owned())));
+        assert!(convert_string_to_ast("a = 'F", &context).is_err());
+    }
+
+    #[test]
+    fn convert_positions() {
+        let input = "d[-1] = len";
+
+        let expected_result = Ok(Box::new(Expression::BinaryExp(BinaryExpression {l_value: Box::new(Expression::EndVariable(EndVariable {offset: 1})), operator: Operator::Boolean(Equal), modulus: None, r_value: Box::new(Expression::Length)})));
+        assert_eq!(convert(input), expected_result);
+
+        let expected_result = Ok(Box::new(Expression::BinaryExp(BinaryExpression {l_value: Box::new(Expression::Variable(Variable {index: 1})), operator: Operator::Boolean(Equal), modulus: None, r_value: Box::new(Expression::Value(Value::Numerical(1)))})));
+        assert_eq!(convert("d[2] = 1"), expected_result);
+        assert!(convert("d[5] = 1").is_err());
+        assert!(convert("d[-5] = 1").is_err());
+        assert!(convert("d[0] = 1").is_err());
+        assert!(convert("lens = 1").is_err());
+        assert_eq!(convert("ab = 1"), Err(RuleError::UnknownName("ab".to_owned())));
     }
-}
 
+    #[test]
+    fn convert_chained_comparison() {
+        assert_eq!(convert("a = b = c"), Err(RuleError::NotNumbers));
+    }
+
+    #[test]
+    fn convert_predicates() {
+        let alphabet = Alphabet::from_chars("12345").unwrap();
+        let lock = Lock::PushButton { ordered: true };
+        let context = Context { total_vars: 5, alphabet: &alphabet, lock: &lock };
+
+        let expected_result = Ok(Box::new(Expression::Not(Box::new(Expression::Predicate(Predicate::Together(0, 3))))));
+        assert_eq!(convert_string_to_ast("!together(1, 4)", &context), expected_result);
+
+        let expected_result = Ok(Box::new(Expression::BinaryExp(BinaryExpression {l_value: Box::new(Expression::Predicate(Predicate::Count)), operator: Operator::Boolean(GreaterThan), modulus: None, r_value: Box::new(Expression::Value(Value::Numerical(2)))})));
+        assert_eq!(convert_string_to_ast("count > 2", &context), expected_result);
+
+        assert!(convert_string_to_ast("count", &context).is_err());
+        assert!(convert_string_to_ast("pressed(1) = 1", &context).is_err());
+        assert!(convert_string_to_ast("!count = 2", &context).is_err());
+        assert!(convert_string_to_ast("pressed(1,)", &context).is_err());
+        assert!(convert("pressed(1)").is_err());
+    }
+
+    #[test]
+    fn convert_dial_arithmetic() {
+        let alphabet = Alphabet::decimal();
+        let lock = Lock::Dial { size: 40, tolerance: 0 };
+        let context = Context { total_vars: 3, alphabet: &alphabet, lock: &lock };
+
+        let sum = Expression::BinaryExp(BinaryExpression {l_value: Box::new(Expression::Variable(Variable {index: 0})), operator: Operator::Binary(Add), modulus: Some(40), r_value: Box::new(Expression::Value(Value::Numerical(2)))});
+        let remainder = Expression::BinaryExp(BinaryExpression {l_value: Box::new(Expression::Variable(Variable {index: 2})), operator: Operator::Binary(Modulo), modulus: Some(40), r_value: Box::new(Expression::Value(Value::Numerical(4)))});
+        let expected_result = Ok(Box::new(Expression::BinaryExp(BinaryExpression {l_value: Box::new(sum), operator: Operator::Boolean(Equal), modulus: None, r_value: Box::new(remainder)})));
+        assert_eq!(convert_string_to_ast("a + 2 = c % 4", &context), expected_result);
+    }
+}
@@ -5,26 +5,21 @@ pub enum BinaryOperator {
     Add,
     Subtract,
     Multiply,
+    Modulo,
+}
+
+impl BinaryOperator {
+    fn precedence(&self) -> u8 {
+        match *self {
+            BinaryOperator::Add | BinaryOperator::Subtract => 0,
+            BinaryOperator::Multiply | BinaryOperator::Modulo => 1,
+        }
+    }
 }
 
 impl PartialEq for BinaryOperator {
     fn eq(&self, other: &BinaryOperator) -> bool {
-        match self {
-            &BinaryOperator::Add | &BinaryOperator::Subtract => {
-                if let &BinaryOperator::Multiply = other {
-                    false
-                } else {
-                    true
-                }
-            },
-            _ => {
-                if let &BinaryOperator::Multiply = other {
-                    true
-                } else {
-                    false
-                }
-            }
-        }
+        self.precedence() == other.precedence()
     }
 }
 
@@ -36,12 +31,7 @@ impl PartialOrd for BinaryOperator {
 
 impl Ord for BinaryOperator {
     fn cmp(&self, other: &BinaryOperator) -> Ordering {
-        match (self, other) {
-            (&BinaryOperator::Multiply, &BinaryOperator::Multiply) => Ordering::Equal,
-            (&BinaryOperator::Multiply, _) => Ordering::Greater,
-            (_, &BinaryOperator::Multiply) => Ordering::Less,
-            (_, _) => Ordering::Equal,
-        }
+        self.precedence().cmp(&other.precedence())
     }
 }
 
@@ -57,6 +47,13 @@ mod tests {
         assert_ne!(m, a); assert_ne!(m, s); assert_eq!(m, m);
     }
 
+    #[test]
+    fn modulo_matches_multiply() {
+        let (a, m, r) = (BinaryOperator::Add, BinaryOperator::Multiply, BinaryOperator::Modulo);
+        assert_eq!(r, m); assert_ne!(r, a);
+        assert!(r > a); assert!(r <= m); assert!(r >= m);
+    }
+
     #[test]
     fn priority() {
         let (a, s, m) = (BinaryOperator::Add, BinaryOperator::Subtract, BinaryOperator::Multiply);
@@ -10,6 +10,21 @@ pub enum Operator {
     Binary(BinaryOperator),
 }
 
+impl Operator {
+    /// The character the operator is written with in rules.
+    pub fn symbol(&self) -> char {
+        match *self {
+            Operator::Boolean(BooleanOperator::Equal) => '=',
+            Operator::Boolean(BooleanOperator::GreaterThan) => '>',
+            Operator::Boolean(BooleanOperator::LessThan) => '<',
+            Operator::Binary(BinaryOperator::Add) => '+',
+            Operator::Binary(BinaryOperator::Subtract) => '-',
+            Operator::Binary(BinaryOperator::Multiply) => '*',
+            Operator::Binary(BinaryOperator::Modulo) => '%',
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,236 @@
+use ast::evaluable::{Value, Evaluable};
+use error::{EvalError, RuleError};
+use ast::Context;
+use alphabet::Alphabet;
+use lock::Lock;
+use lock::pattern;
+use strategy;
+
+/// A built-in function of the whole combination. Which predicates a
+/// rule can use depends on the kind of lock the passcode is for.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Predicate {
+    /// `pressed(x)`: button `x` is pressed.
+    Pressed(usize),
+    /// `together(x, y)`: buttons `x` and `y` are pressed at the same time.
+    Together(usize, usize),
+    /// `step(x)`: the step button `x` is pressed at, 0 if it isn't pressed.
+    Step(usize),
+    /// `count`: how many buttons are pressed.
+    Count,
+    /// `starts_at_corner`: the pattern starts at one of the grid's corners.
+    StartsAtCorner,
+    /// `crosses`: two lines of the pattern cross each other.
+    Crosses,
+    /// `turns`: how many times the pattern changes direction.
+    Turns,
+    /// `exact(x, y, ...)`: how many positions hold the same symbol as
+    /// they do in the guess `x y ...`, like the black pegs of Mastermind.
+    Exact(Vec<u8>),
+    /// `misplaced(x, y, ...)`: how many more of the guess's symbols are
+    /// in the combination at other positions, like the white pegs.
+    Misplaced(Vec<u8>),
+}
+
+impl Predicate {
+    pub fn is_boolean(&self) -> bool {
+        match *self {
+            Predicate::Pressed(_) | Predicate::Together(_, _) | Predicate::StartsAtCorner | Predicate::Crosses => true,
+            Predicate::Step(_) | Predicate::Count | Predicate::Turns | Predicate::Exact(_) | Predicate::Misplaced(_) => false,
+        }
+    }
+
+    /// Writes the predicate out the way it's used in rules, naming
+    /// buttons by their symbols in `alphabet`.
+    pub fn render(&self, alphabet: &Alphabet) -> String {
+        let label = |button: usize| argument(alphabet.symbol(button).unwrap_or("?"));
+        match *self {
+            Predicate::Pressed(button) => format!("pressed({})", label(button)),
+            Predicate::Together(x, y) => format!("together({}, {})", label(x), label(y)),
+            Predicate::Step(button) => format!("step({})", label(button)),
+            Predicate::Count => "count".to_owned(),
+            Predicate::StartsAtCorner => "starts_at_corner".to_owned(),
+            Predicate::Crosses => "crosses".to_owned(),
+            Predicate::Turns => "turns".to_owned(),
+            Predicate::Exact(ref guess) => format!("exact({})", symbols(guess, alphabet)),
+            Predicate::Misplaced(ref guess) => format!("misplaced({})", symbols(guess, alphabet)),
+        }
+    }
+}
+
+impl Evaluable for Predicate {
+    fn evaluate(&self, arr: &[u8]) -> Result<Value, EvalError> {
+        match *self {
+            Predicate::Pressed(button) => Ok(Value::Boolean(step(arr, button)? != 0)),
+            Predicate::Together(x, y) => {
+                let (step_x, step_y) = (step(arr, x)?, step(arr, y)?);
+                Ok(Value::Boolean(step_x != 0 && step_x == step_y))
+            },
+            Predicate::Step(button) => Ok(Value::Numerical(step(arr, button)? as i64)),
+            Predicate::Count => Ok(Value::Numerical(arr.iter().filter(|s| **s != 0).count() as i64)),
+            Predicate::StartsAtCorner => Ok(Value::Boolean(pattern::starts_at_corner(arr))),
+            Predicate::Crosses => Ok(Value::Boolean(pattern::crosses(arr))),
+            Predicate::Turns => Ok(Value::Numerical(pattern::turns(arr) as i64)),
+            Predicate::Exact(ref guess) => Ok(Value::Numerical(strategy::feedback(guess, arr).exact as i64)),
+            Predicate::Misplaced(ref guess) => Ok(Value::Numerical(strategy::feedback(guess, arr).misplaced as i64)),
+        }
+    }
+}
+
+fn symbols(values: &[u8], alphabet: &Alphabet) -> String {
+    values.iter().map(|v| argument(alphabet.symbol_for(*v).unwrap_or("?"))).collect::<Vec<String>>().join(", ")
+}
+
+/// Writes a symbol as an argument of a predicate, quoting it when it
+/// holds a comma, a parenthesis, a space or a quote, which the parser
+/// would otherwise take apart.
+fn argument(symbol: &str) -> String {
+    if !symbol.chars().any(|c| c == ',' || c == ')' || c == '\'' || c == '"' || c.is_whitespace()) {
+        symbol.to_owned()
+    } else if symbol.contains('\'') {
+        format!("\"{}\"", symbol)
+    } else {
+        format!("'{}'", symbol)
+    }
+}
+
+fn step(arr: &[u8], button: usize) -> Result<u8, EvalError> {
+    arr.get(button).cloned().ok_or(EvalError::MissingPosition)
+}
+
+/// Every true or false predicate a rule can use for `lock`, with each
+/// button or pair of buttons of `alphabet` it can take.
+pub fn library(lock: &Lock, alphabet: &Alphabet) -> Vec<Predicate> {
+    match *lock {
+        Lock::PushButton { .. } => {
+            let buttons = alphabet.len();
+            let mut predicates: Vec<Predicate> = (0..buttons).map(Predicate::Pressed).collect();
+            for x in 0..buttons {
+                predicates.extend((x + 1..buttons).map(|y| Predicate::Together(x, y)));
+            }
+            predicates
+        },
+        Lock::Pattern => vec![Predicate::StartsAtCorner, Predicate::Crosses],
+        Lock::Code | Lock::Dial { .. } => Vec::new(),
+    }
+}
+
+/// Looks up the predicate called `name` for the context's kind of lock.
+/// Returns `Ok(None)` when there's no predicate by that name.
+pub fn resolve(name: &str, args: &[String], context: &Context) -> Result<Option<Predicate>, RuleError> {
+    match *context.lock {
+        Lock::PushButton { .. } => {
+            let arity = match name {
+                "count" => 0,
+                "pressed" | "step" => 1,
+                "together" => 2,
+                _ => return Ok(None),
+            };
+            if args.len() != arity {
+                return Err(RuleError::WrongArity { name: name.to_owned(), expected: arity, given: args.len() });
+            }
+            let mut buttons: Vec<usize> = Vec::with_capacity(arity);
+            for arg in args {
+                match context.alphabet.index_of(arg) {
+                    Some(button) => buttons.push(button),
+                    None => return Err(RuleError::UnknownButton(arg.clone())),
+                }
+            }
+            Ok(Some(match name {
+                "count" => Predicate::Count,
+                "pressed" => Predicate::Pressed(buttons[0]),
+                "step" => Predicate::Step(buttons[0]),
+                _ => Predicate::Together(buttons[0], buttons[1]),
+            }))
+        },
+        Lock::Pattern => {
+            let predicate = match name {
+                "starts_at_corner" => Predicate::StartsAtCorner,
+                "crosses" => Predicate::Crosses,
+                "turns" => Predicate::Turns,
+                _ => return Ok(None),
+            };
+            if !args.is_empty() {
+                return Err(RuleError::NoArguments(name.to_owned()));
+            }
+            Ok(Some(predicate))
+        },
+        Lock::Code | Lock::Dial { .. } => {
+            let exact = match name {
+                "exact" => true,
+                "misplaced" => false,
+                _ => return Ok(None),
+            };
+            check_guess(name, args.len(), context)?;
+            let mut guess: Vec<u8> = Vec::with_capacity(args.len());
+            for arg in args {
+                match context.alphabet.symbol_value(arg) {
+                    Some(value) => guess.push(value),
+                    None => return Err(RuleError::UnknownSymbol(arg.clone())),
+                }
+            }
+            Ok(Some(if exact { Predicate::Exact(guess) } else { Predicate::Misplaced(guess) }))
+        },
+    }
+}
+
+/// Checks that a guess of `length` symbols can be given to `exact` or
+/// `misplaced`, which only codes and dials have.
+pub fn check_guess(name: &str, length: usize, context: &Context) -> Result<(), RuleError> {
+    match *context.lock {
+        Lock::Code | Lock::Dial { .. } => (),
+        Lock::PushButton { .. } | Lock::Pattern => return Err(RuleError::UnknownName(name.to_owned())),
+    }
+    if length == 0 || length > context.total_vars {
+        return Err(RuleError::GuessLength { name: name.to_owned(), positions: context.total_vars, given: length });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast::code_context;
+
+    #[test]
+    fn button_predicates() {
+        let combination = [1, 0, 2, 1, 0];
+        assert_eq!(Predicate::Pressed(0).evaluate(&combination), Ok(Value::Boolean(true)));
+        assert_eq!(Predicate::Pressed(1).evaluate(&combination), Ok(Value::Boolean(false)));
+        assert_eq!(Predicate::Together(0, 3).evaluate(&combination), Ok(Value::Boolean(true)));
+        assert_eq!(Predicate::Together(1, 4).evaluate(&combination), Ok(Value::Boolean(false)));
+        assert_eq!(Predicate::Step(2).evaluate(&combination), Ok(Value::Numerical(2)));
+        assert_eq!(Predicate::Count.evaluate(&combination), Ok(Value::Numerical(3)));
+    }
+
+    #[test]
+    fn resolve_buttons() {
+        let alphabet = Alphabet::from_chars("12345").unwrap();
+        let lock = Lock::PushButton { ordered: true };
+        let context = Context { total_vars: 5, alphabet: &alphabet, lock: &lock };
+        let args = vec!["1".to_owned(), "4".to_owned()];
+        assert_eq!(resolve("together", &args, &context), Ok(Some(Predicate::Together(0, 3))));
+        assert!(resolve("pressed", &args, &context).is_err());
+        assert!(resolve("pressed", &["6".to_owned()], &context).is_err());
+        assert_eq!(resolve("count", &[], &context), Ok(Some(Predicate::Count)));
+        assert_eq!(resolve("unknown", &[], &context), Ok(None));
+
+        let code_context = Context { total_vars: 5, alphabet: &alphabet, lock: &Lock::Code };
+        assert_eq!(resolve("count", &[], &code_context), Ok(None));
+    }
+
+    #[test]
+    fn guess_feedback() {
+        let context = code_context(4);
+        let guess: Vec<String> = "1 2 3 4".split(' ').map(|s| s.to_owned()).collect();
+        let exact = resolve("exact", &guess, &context).unwrap().unwrap();
+        assert_eq!(exact, Predicate::Exact(vec![1, 2, 3, 4]));
+        assert_eq!(exact.render(context.alphabet), "exact(1, 2, 3, 4)");
+        assert_eq!(exact.evaluate(&[1, 3, 2, 5]), Ok(Value::Numerical(1)));
+        let misplaced = resolve("misplaced", &guess, &context).unwrap().unwrap();
+        assert_eq!(misplaced.evaluate(&[1, 3, 2, 5]), Ok(Value::Numerical(2)));
+        let long: Vec<String> = (0..5).map(|i| i.to_string()).collect();
+        assert!(resolve("exact", &long, &context).is_err());
+        assert!(resolve("exact", &["x".to_owned()], &context).is_err());
+    }
+}
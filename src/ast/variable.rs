@@ -1,16 +1,71 @@
 use ast::evaluable::{Value, Evaluable};
+use error::EvalError;
 
+/// A position counted from the start of the combination, from 0, which
+/// rules write as `a` to `z` and then `d[27]`, `d[28]`, ...
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Variable {
-    pub name: char,
+    pub index: usize,
+}
+
+impl Variable {
+    /// The index into the combination this variable reads, if the
+    /// combination is long enough to have it.
+    pub fn position(&self, length: usize) -> Option<usize> {
+        if self.index < length {
+            Some(self.index)
+        } else {
+            None
+        }
+    }
 }
 
 impl Evaluable for Variable {
-    fn evaluate(&self, arr: &Vec<u8>) -> Result<Value, &'static str> {
-        match self.name {
-            c @ 'a'...'z' => Ok(Value::Numerical(arr[(c as usize) - 97])),
-            _ => Err("Incorrect variable name"),
+    fn evaluate(&self, arr: &[u8]) -> Result<Value, EvalError> {
+        match self.position(arr.len()) {
+            Some(i) => Ok(Value::Numerical(arr[i] as i64)),
+            None => Err(EvalError::MissingPosition),
+        }
+    }
+}
+
+/// A position counted from the end of the combination, written as
+/// `d[-1]` for the last position, `d[-2]` for the one before it, etc.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EndVariable {
+    pub offset: usize,
+}
+
+impl EndVariable {
+    pub fn position(&self, length: usize) -> Option<usize> {
+        if self.offset >= 1 && self.offset <= length {
+            Some(length - self.offset)
+        } else {
+            None
         }
     }
 }
 
+impl Evaluable for EndVariable {
+    fn evaluate(&self, arr: &[u8]) -> Result<Value, EvalError> {
+        match self.position(arr.len()) {
+            Some(i) => Ok(Value::Numerical(arr[i] as i64)),
+            None => Err(EvalError::MissingPosition),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_positions() {
+        assert_eq!(Variable { index: 2 }.evaluate(&[1, 2, 3]), Ok(Value::Numerical(3)));
+        assert_eq!(Variable { index: 3 }.evaluate(&[1, 2, 3]), Err(EvalError::MissingPosition));
+        assert_eq!(Variable { index: 27 }.evaluate(&[7; 28]), Ok(Value::Numerical(7)));
+        assert_eq!(EndVariable { offset: 1 }.evaluate(&[1, 2, 3]), Ok(Value::Numerical(3)));
+        assert_eq!(EndVariable { offset: 3 }.evaluate(&[1, 2, 3]), Ok(Value::Numerical(1)));
+        assert_eq!(EndVariable { offset: 4 }.evaluate(&[1, 2, 3]), Err(EvalError::MissingPosition));
+    }
+}
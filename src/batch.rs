@@ -0,0 +1,40 @@
+/// A batch of candidates laid out by position, so that a rule can be
+/// checked against the whole batch one position at a time, in loops
+/// over plain slices the compiler can vectorise.
+pub struct Batch {
+    /// The value at each position, one column per position.
+    pub columns: Vec<Vec<u8>>,
+    /// The length of each candidate. Positions past a candidate's
+    /// length hold nothing meaningful.
+    pub lengths: Vec<u8>,
+    len: usize,
+}
+
+/// How many candidates go into a batch.
+pub const BATCH_SIZE: usize = 4096;
+
+impl Batch {
+    pub fn new(max_length: usize) -> Batch {
+        Batch {
+            columns: vec![vec![0; BATCH_SIZE]; max_length],
+            lengths: vec![0; BATCH_SIZE],
+            len: 0,
+        }
+    }
+
+    /// The number of candidates in the batch.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn set_len(&mut self, len: usize) {
+        assert!(len <= BATCH_SIZE);
+        self.len = len;
+    }
+
+    /// Writes a candidate of the batch into `buffer`.
+    pub fn row(&self, k: usize, buffer: &mut Vec<u8>) {
+        buffer.clear();
+        buffer.extend(self.columns[..self.lengths[k] as usize].iter().map(|column| column[k]));
+    }
+}
@@ -0,0 +1,339 @@
+use std::thread;
+use space::Space;
+
+/// The candidates of a `Space` that are still possible, kept as one bit
+/// per candidate index along with a running count. The candidates that
+/// have been removed are kept in a bitset for each tag they were removed
+/// with, so they can be put back later. Nothing is allocated until the
+/// first candidate is removed.
+pub struct CandidateSet {
+    size: u64,
+    count: u64,
+    record: Option<Record>,
+}
+
+struct Record {
+    alive: Vec<u64>,
+    /// Each tag that removed any index, with a bitset of the indices it
+    /// removed. No index is in more than one of them.
+    removed: Removed,
+}
+
+/// Bitsets of the indices each tag removed.
+type Removed = Vec<(u32, Vec<u64>)>;
+
+/// The same run of words of each tag's bitset, handed to the thread
+/// that sweeps over that run.
+type Run<'a> = Vec<(u32, &'a mut [u64])>;
+
+/// The indices a sweep over the set hands out: the ones still alive, or
+/// the ones in a bitset of removed indices.
+#[derive(Clone, Copy)]
+enum Selection<'a> {
+    Alive,
+    Removed(&'a [u64]),
+}
+
+impl CandidateSet {
+    /// A set holding every index below `size`.
+    pub fn full(size: u64) -> CandidateSet {
+        CandidateSet { size, count: size, record: None }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.count
+    }
+
+    /// Keeps only the indices `verdict` leaves alone, handing it the
+    /// indices in increasing order, in batches of up to `size` along
+    /// with a tag for each that starts out as 0. Setting a tag, which
+    /// must be one of `tags`, removes the index, and is recorded as what
+    /// removed it. The indices are
+    /// split into contiguous runs across up to `threads` threads, each
+    /// with a `verdict` of its own from `make_verdict`, which leaves the
+    /// same set behind however many threads there are. Returns how many
+    /// were removed.
+    pub fn retain_batches<M, F>(&mut self, tags: &[u32], size: usize, threads: usize, make_verdict: M) -> u64
+        where M: Fn() -> F + Sync, F: FnMut(&[u64], &mut [u32])
+    {
+        if self.record.is_none() {
+            self.record = Some(Record { alive: full_words(self.size), removed: Vec::new() });
+        }
+        let record = self.record.as_mut().unwrap();
+        let (removed, _) = sweep(&mut record.alive, &mut record.removed, tags, Selection::Alive, size, threads, make_verdict);
+        self.count -= removed;
+        removed
+    }
+
+    /// Hands the indices that were removed with the tag `tag` back to
+    /// `verdict` in the same way as `retain_batches`, putting back the
+    /// ones whose tag it leaves as 0 and retagging the rest with one of
+    /// `tags`. Returns how many were put back.
+    pub fn restore_batches<M, F>(&mut self, tag: u32, tags: &[u32], size: usize, threads: usize, make_verdict: M) -> u64
+        where M: Fn() -> F + Sync, F: FnMut(&[u64], &mut [u32])
+    {
+        let record = match self.record {
+            Some(ref mut record) => record,
+            None => return 0,
+        };
+        let bits = match record.removed.iter().position(|&(t, _)| t == tag) {
+            Some(position) => record.removed.swap_remove(position).1,
+            None => return 0,
+        };
+        let (_, restored) = sweep(&mut record.alive, &mut record.removed, tags, Selection::Removed(&bits), size, threads, make_verdict);
+        self.count += restored;
+        restored
+    }
+
+    /// The indices in the set, in increasing order.
+    pub fn iter(&self) -> Indices<'_> {
+        Indices { set: self, next: 0 }
+    }
+}
+
+/// Sweeps over the selected indices, split into runs of words across
+/// threads, and records the indices removed in `removed`. Each of `tags`
+/// gets a bitset for the whole set up front, which every thread writes
+/// its own run of, and the ones left empty are dropped afterwards.
+/// Returns how many indices were removed and how many were put back.
+fn sweep<M, F>(alive: &mut [u64], removed: &mut Removed, tags: &[u32], selection: Selection, size: usize, threads: usize, make_verdict: M) -> (u64, u64)
+    where M: Fn() -> F + Sync, F: FnMut(&[u64], &mut [u32])
+{
+    let total = alive.len();
+    for &tag in tags {
+        if !removed.iter().any(|&(t, _)| t == tag) {
+            removed.push((tag, vec![0; total]));
+        }
+    }
+    // Every thread gets at least a batch's worth of indices.
+    let chunk = total.div_ceil(threads.max(1)).max(size.div_ceil(64)).max(1);
+    let mut runs: Vec<Run> = (0..total.div_ceil(chunk).max(1)).map(|_| Vec::new()).collect();
+    for &mut (tag, ref mut bits) in removed.iter_mut().filter(|&&mut (t, _)| tags.contains(&t)) {
+        for (run, words) in runs.iter_mut().zip(bits.chunks_mut(chunk)) {
+            run.push((tag, words));
+        }
+    }
+    let counts = if total <= chunk {
+        sweep_words(alive, &mut runs[0], 0, size, selection, make_verdict())
+    } else {
+        thread::scope(|scope| {
+            let make_verdict = &make_verdict;
+            let handles: Vec<_> = alive.chunks_mut(chunk).zip(runs).enumerate().map(|(i, (words, mut run))| {
+                let selection = match selection {
+                    Selection::Alive => Selection::Alive,
+                    Selection::Removed(bits) => Selection::Removed(&bits[i * chunk..(i * chunk + words.len())]),
+                };
+                scope.spawn(move || sweep_words(words, &mut run, (i * chunk) as u64 * 64, size, selection, make_verdict()))
+            }).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).fold((0, 0), |total, counts| (total.0 + counts.0, total.1 + counts.1))
+        })
+    };
+    removed.retain(|(_, bits)| bits.iter().any(|word| *word != 0));
+    counts
+}
+
+/// Sweeps over the selected indices of a run of words, the first of
+/// which holds the index `first`, recording the ones removed in `run`.
+/// Returns how many indices were removed and how many were put back.
+fn sweep_words<F>(words: &mut [u64], run: &mut Run, first: u64, size: usize, selection: Selection, mut verdict: F) -> (u64, u64)
+    where F: FnMut(&[u64], &mut [u32])
+{
+    let mut indices: Vec<u64> = Vec::with_capacity(size);
+    let mut tags: Vec<u32> = vec![0; size];
+    let mut counts = (0, 0);
+    for w in 0..words.len() {
+        let mut remaining = match selection {
+            Selection::Alive => words[w],
+            Selection::Removed(bits) => bits[w],
+        };
+        while remaining != 0 {
+            let bit = remaining.trailing_zeros() as u64;
+            remaining &= remaining - 1;
+            indices.push(first + w as u64 * 64 + bit);
+            if indices.len() == size {
+                flush(words, run, first, &mut indices, &mut tags, &mut verdict, &mut counts);
+            }
+        }
+    }
+    flush(words, run, first, &mut indices, &mut tags, &mut verdict, &mut counts);
+    counts
+}
+
+/// Runs `verdict` over a batch of indices, then removes the ones it
+/// tagged and puts back the removed ones it didn't, adding them to the
+/// counts of indices removed and put back.
+fn flush<F>(words: &mut [u64], run: &mut Run, first: u64, indices: &mut Vec<u64>, tags: &mut [u32], verdict: &mut F, counts: &mut (u64, u64))
+    where F: FnMut(&[u64], &mut [u32])
+{
+    if indices.is_empty() {
+        return;
+    }
+    let tags = &mut tags[..indices.len()];
+    for tag in tags.iter_mut() {
+        *tag = 0;
+    }
+    verdict(indices, tags);
+    for (index, tag) in indices.iter().zip(tags.iter()) {
+        let index = index - first;
+        let (word, bit) = ((index / 64) as usize, 1 << (index % 64));
+        let alive = words[word] & bit != 0;
+        if *tag != 0 {
+            if alive {
+                words[word] &= !bit;
+                counts.0 += 1;
+            }
+            let bits = run.iter_mut().find(|&&mut (t, _)| t == *tag).expect("Tagged with a tag it wasn't given");
+            bits.1[word] |= bit;
+        } else if !alive {
+            words[word] |= bit;
+            counts.1 += 1;
+        }
+    }
+    indices.clear();
+}
+
+fn full_words(size: u64) -> Vec<u64> {
+    let mut words = vec![!0u64; size.div_ceil(64) as usize];
+    if !size.is_multiple_of(64) {
+        *words.last_mut().unwrap() = (1 << (size % 64)) - 1;
+    }
+    words
+}
+
+pub struct Indices<'a> {
+    set: &'a CandidateSet,
+    next: u64,
+}
+
+impl<'a> Iterator for Indices<'a> {
+    type Item = u64;
+    fn next(&mut self) -> Option<u64> {
+        let words = match self.set.record {
+            Some(ref record) => &record.alive,
+            None => {
+                if self.next >= self.set.size {
+                    return None;
+                }
+                self.next += 1;
+                return Some(self.next - 1);
+            },
+        };
+        while self.next < self.set.size {
+            let w = (self.next / 64) as usize;
+            let word = words[w] & (!0u64 << (self.next % 64));
+            if word != 0 {
+                let index = w as u64 * 64 + word.trailing_zeros() as u64;
+                self.next = index + 1;
+                return Some(index);
+            }
+            self.next = (w as u64 + 1) * 64;
+        }
+        None
+    }
+}
+
+/// Decodes the candidates of a set as they're iterated over.
+pub struct Candidates<'a> {
+    pub space: &'a Space,
+    pub indices: Indices<'a>,
+}
+
+impl<'a> Iterator for Candidates<'a> {
+    type Item = Vec<u8>;
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let index = self.indices.next()?;
+        let mut combination = Vec::with_capacity(self.space.max_length());
+        self.space.decode(index, &mut combination);
+        Some(combination)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// The tags the set keeps a record of removed indices for.
+    fn tags(set: &CandidateSet) -> Vec<u32> {
+        set.record.as_ref().map_or(Vec::new(), |record| record.removed.iter().map(|&(tag, _)| tag).collect())
+    }
+
+    #[test]
+    fn retain_and_iterate() {
+        let mut set = CandidateSet::full(130);
+        assert_eq!(set.len(), 130);
+        assert_eq!(set.iter().count(), 130);
+        let removed = set.retain_batches(&[1, 2], 7, 1, || |indices: &[u64], tags: &mut [u32]| {
+            for (index, tag) in indices.iter().zip(tags.iter_mut()) {
+                *tag = (index % 3) as u32;
+            }
+        });
+        assert_eq!(removed, 86);
+        assert_eq!(set.len(), 44);
+        assert_eq!(tags(&set), vec![1, 2]);
+        assert_eq!(set.iter().collect::<Vec<u64>>(), (0..130).filter(|i| i % 3 == 0).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn retain_visits_in_order() {
+        let mut set = CandidateSet::full(200);
+        set.retain_batches(&[1, 2], 64, 1, || |indices: &[u64], tags: &mut [u32]| {
+            for (index, tag) in indices.iter().zip(tags.iter_mut()) {
+                *tag = (*index <= 100) as u32;
+            }
+        });
+        // Tag 2 removed nothing, so no record of it is kept.
+        assert_eq!(tags(&set), vec![1]);
+        let visited = Mutex::new(Vec::new());
+        set.retain_batches(&[], 10, 1, || |indices: &[u64], tags: &mut [u32]| {
+            assert!(indices.len() <= 10 && tags.iter().all(|tag| *tag == 0));
+            visited.lock().unwrap().extend_from_slice(indices);
+        });
+        assert_eq!(visited.into_inner().unwrap(), (101..200).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn threads_leave_the_same_set() {
+        let sets: Vec<Vec<u64>> = [1, 2, 3, 8].iter().map(|&threads| {
+            let mut set = CandidateSet::full(10_000);
+            let removed = set.retain_batches(&[1], 64, threads, || |indices: &[u64], tags: &mut [u32]| {
+                for (index, tag) in indices.iter().zip(tags.iter_mut()) {
+                    *tag = (index % 7 != 3) as u32;
+                }
+            });
+            assert_eq!(removed, 10_000 - set.len());
+            set.iter().collect()
+        }).collect();
+        assert_eq!(sets[0], (0..10_000).filter(|i| i % 7 == 3).collect::<Vec<u64>>());
+        assert!(sets.iter().all(|set| *set == sets[0]));
+    }
+
+    #[test]
+    fn restores_what_a_tag_removed() {
+        for &threads in [1, 3].iter() {
+            let mut set = CandidateSet::full(1000);
+            assert_eq!(set.restore_batches(1, &[], 64, threads, || |_: &[u64], _: &mut [u32]| panic!("Nothing was removed")), 0);
+            set.retain_batches(&[1, 2], 64, threads, || |indices: &[u64], tags: &mut [u32]| {
+                for (index, tag) in indices.iter().zip(tags.iter_mut()) {
+                    *tag = if index % 2 == 0 { 1 } else if index % 3 == 0 { 2 } else { 0 };
+                }
+            });
+            assert_eq!(set.len(), 333);
+            // Of the even indices, only the multiples of 6 fail the second
+            // check too, and those get retagged instead of put back.
+            let restored = set.restore_batches(1, &[2], 64, threads, || |indices: &[u64], tags: &mut [u32]| {
+                for (index, tag) in indices.iter().zip(tags.iter_mut()) {
+                    assert_eq!(index % 2, 0);
+                    *tag = if index % 3 == 0 { 2 } else { 0 };
+                }
+            });
+            assert_eq!(restored, 333);
+            assert_eq!(set.len(), 666);
+            assert_eq!(tags(&set), vec![2]);
+            let restored = set.restore_batches(2, &[], 64, threads, || |_: &[u64], _: &mut [u32]| {});
+            assert_eq!(restored, 334);
+            assert_eq!(set.iter().count(), 1000);
+            assert!(tags(&set).is_empty());
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use std::error;
+use std::fmt;
+
+/// Everything that can go wrong working on a `Passcode`. The errors of
+/// each part of the crate have a type of their own, which converts
+/// into this one.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Error {
+    /// A rule couldn't be parsed.
+    Rule { rule: String, error: RuleError },
+    Alphabet(AlphabetError),
+    Mask(MaskError),
+    /// A combination written out couldn't be read.
+    Combination(CombinationError),
+    /// A dial's values must run from 0 up to one less than its size.
+    DialValues { size: usize },
+    /// A passcode longer than `MAX_LENGTH`.
+    TooManyPositions(usize),
+    /// A push-button lock with too many buttons to list every
+    /// combination of, more than `most`.
+    TooManyButtons { buttons: usize, most: usize },
+    /// More candidates than `MAX_CANDIDATES` to go through, this many
+    /// of them, or more than a `u64` can count.
+    TooManyCandidates(Option<u64>),
+    /// There's no rule in place with this id.
+    NoSuchRule(usize),
+    /// No candidate is left, so the rules contradict each other.
+    Contradiction,
+    /// More than one candidate is left, this many of them.
+    Ambiguous(usize),
+}
+
+/// Why a rule couldn't be parsed.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum RuleError {
+    /// A position past the last one the passcode has.
+    InvalidVariable { last: char, found: char },
+    UnknownName(String),
+    NumberTooLarge,
+    /// A quoted symbol that isn't in the alphabet.
+    UnknownSymbol(String),
+    /// An operator without the values it needs on either side.
+    Malformed,
+    Empty,
+    /// A quoted symbol missing its closing quote, quote included.
+    UnterminatedSymbol(String),
+    UnterminatedCall,
+    EmptyArgument,
+    UnexpectedInIndex(char),
+    UnterminatedIndex,
+    /// A `d[...]` index past the positions the passcode has.
+    IndexOutOfRange { index: i64, positions: i64 },
+    InvalidIndex(String),
+    /// A comparison of something other than two numbers.
+    NotNumbers,
+    /// A rule that isn't a comparison or a true or false predicate.
+    NotBoolean,
+    WrongArity { name: String, expected: usize, given: usize },
+    UnknownButton(String),
+    NoArguments(String),
+    /// A guess with no symbols, or more than the passcode has positions.
+    GuessLength { name: String, positions: usize, given: usize },
+}
+
+/// Why a rule couldn't be evaluated for a combination, which counts as
+/// the rule not holding.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EvalError {
+    /// The rule refers to a position the combination doesn't have, such
+    /// as `f` or `d[-5]` on a four symbol combination.
+    MissingPosition,
+    NotANumber,
+    NotABoolean,
+    Overflow,
+    ModuloByZero,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum AlphabetError {
+    TooManySymbols(usize),
+    NoSymbols,
+    EmptySymbol,
+    DuplicateSymbol(String),
+    DuplicateValue(u8),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MaskError {
+    UnknownCharset(char),
+    LoneQuestionMark,
+    NotInAlphabet(char),
+    NoPositions,
+    UnfinishedRange,
+    BackwardsRange(char, char),
+    UnterminatedCharset,
+    EmptyCharset,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum CombinationError {
+    UnknownSymbol(String),
+    UnknownButton(String),
+    PressedTwice(String),
+    UnknownValue(u8),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Rule { ref rule, ref error } => write!(f, "{}: {}", rule, error),
+            Error::Alphabet(ref error) => error.fmt(f),
+            Error::Mask(ref error) => error.fmt(f),
+            Error::Combination(ref error) => error.fmt(f),
+            Error::DialValues { size } => write!(f, "A dial's values must run from 0 to {}", size - 1),
+            Error::TooManyPositions(length) => write!(f, "A passcode may have at most 255 positions, {} were given", length),
+            Error::TooManyButtons { buttons, most } => write!(f, "This lock may have at most {} buttons, {} were given", most, buttons),
+            Error::TooManyCandidates(Some(count)) => write!(f, "{} combinations are too many to go through, search for solutions instead", count),
+            Error::TooManyCandidates(None) => write!(f, "Too many combinations to go through, search for solutions instead"),
+            Error::NoSuchRule(id) => write!(f, "There's no rule {}", id),
+            Error::Contradiction => write!(f, "No combination satisfies every rule"),
+            Error::Ambiguous(count) => write!(f, "{} combinations are still possible", count),
+        }
+    }
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RuleError::InvalidVariable { last, found } => write!(f, "Invalid variable added. You may only use 'a' through '{}'. You used: '{}'", last, found),
+            RuleError::UnknownName(ref name) => write!(f, "Unknown name in rule: '{}'", name),
+            RuleError::NumberTooLarge => write!(f, "Number in rule is too large"),
+            RuleError::UnknownSymbol(ref symbol) => write!(f, "Unknown symbol: '{}'", symbol),
+            RuleError::Malformed => write!(f, "Malformed input string"),
+            RuleError::Empty => write!(f, "The rule is empty"),
+            RuleError::UnterminatedSymbol(ref symbol) => write!(f, "Unterminated symbol literal: {}", symbol),
+            RuleError::UnterminatedCall => write!(f, "Unterminated predicate call, expected ')'"),
+            RuleError::EmptyArgument => write!(f, "Empty argument in predicate call"),
+            RuleError::UnexpectedInIndex(c) => write!(f, "Unexpected '{}' in position index", c),
+            RuleError::UnterminatedIndex => write!(f, "Unterminated position index, expected ']'"),
+            RuleError::IndexOutOfRange { index, positions } => write!(f, "Invalid position index: d[{}]. Positions run from 1 to {} or -1 to -{}", index, positions, positions),
+            RuleError::InvalidIndex(ref index) => write!(f, "Invalid position index: d[{}]", index),
+            RuleError::NotNumbers => write!(f, "Malformed AST, only numbers can be compared"),
+            RuleError::NotBoolean => write!(f, "Malformed AST, a boolean operator ('=', '<', '>') is required"),
+            RuleError::WrongArity { ref name, expected, given } => write!(f, "'{}' takes {} button(s), {} given", name, expected, given),
+            RuleError::UnknownButton(ref button) => write!(f, "Unknown button: '{}'", button),
+            RuleError::NoArguments(ref name) => write!(f, "'{}' doesn't take any arguments", name),
+            RuleError::GuessLength { ref name, positions, given } => write!(f, "'{}' takes a guess of 1 to {} symbols, {} given", name, positions, given),
+        }
+    }
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EvalError::MissingPosition => write!(f, "Position is missing from the combination"),
+            EvalError::NotANumber => write!(f, "Not a number"),
+            EvalError::NotABoolean => write!(f, "Not a bool"),
+            EvalError::Overflow => write!(f, "Arithmetic overflow"),
+            EvalError::ModuloByZero => write!(f, "Modulo by zero"),
+        }
+    }
+}
+
+impl fmt::Display for AlphabetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AlphabetError::TooManySymbols(count) => write!(f, "An alphabet may have at most 256 symbols, {} were given", count),
+            AlphabetError::NoSymbols => write!(f, "An alphabet needs at least one symbol"),
+            AlphabetError::EmptySymbol => write!(f, "Alphabet symbols can't be empty"),
+            AlphabetError::DuplicateSymbol(ref symbol) => write!(f, "Duplicate symbol in alphabet: '{}'", symbol),
+            AlphabetError::DuplicateValue(value) => write!(f, "Duplicate value in alphabet: {}", value),
+        }
+    }
+}
+
+impl fmt::Display for MaskError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MaskError::UnknownCharset(c) => write!(f, "Unknown charset in mask: '?{}'", c),
+            MaskError::LoneQuestionMark => write!(f, "Mask can't end with a lone '?'"),
+            MaskError::NotInAlphabet(symbol) => write!(f, "The mask uses '{}', which isn't in the alphabet", symbol),
+            MaskError::NoPositions => write!(f, "A mask needs at least one position"),
+            MaskError::UnfinishedRange => write!(f, "Unfinished range in mask charset"),
+            MaskError::BackwardsRange(start, end) => write!(f, "Backwards range in mask charset: {}-{}", start, end),
+            MaskError::UnterminatedCharset => write!(f, "Unterminated charset in mask, expected ']'"),
+            MaskError::EmptyCharset => write!(f, "Empty charset in mask"),
+        }
+    }
+}
+
+impl fmt::Display for CombinationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CombinationError::UnknownSymbol(ref symbol) => write!(f, "Unknown symbol: '{}'", symbol),
+            CombinationError::UnknownButton(ref button) => write!(f, "Unknown button: '{}'", button),
+            CombinationError::PressedTwice(ref button) => write!(f, "Button '{}' is pressed more than once", button),
+            CombinationError::UnknownValue(value) => write!(f, "No symbol has the value {}", value),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Rule { ref error, .. } => Some(error),
+            Error::Alphabet(ref error) => Some(error),
+            Error::Mask(ref error) => Some(error),
+            Error::Combination(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl error::Error for RuleError {}
+impl error::Error for EvalError {}
+impl error::Error for AlphabetError {}
+impl error::Error for MaskError {}
+impl error::Error for CombinationError {}
+
+impl From<AlphabetError> for Error {
+    fn from(error: AlphabetError) -> Error {
+        Error::Alphabet(error)
+    }
+}
+
+impl From<MaskError> for Error {
+    fn from(error: MaskError) -> Error {
+        Error::Mask(error)
+    }
+}
+
+impl From<CombinationError> for Error {
+    fn from(error: CombinationError) -> Error {
+        Error::Combination(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as StdError;
+
+    #[test]
+    fn messages_and_sources() {
+        let error = Error::Rule { rule: "a = 'G'".to_owned(), error: RuleError::UnknownSymbol("G".to_owned()) };
+        assert_eq!(error.to_string(), "a = 'G': Unknown symbol: 'G'");
+        assert_eq!(error.source().unwrap().to_string(), "Unknown symbol: 'G'");
+        let error: Error = MaskError::BackwardsRange('3', '0').into();
+        assert_eq!(error.to_string(), "Backwards range in mask charset: 3-0");
+        assert!(Error::NoSuchRule(4).source().is_none());
+    }
+}
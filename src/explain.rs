@@ -0,0 +1,102 @@
+use std::fmt;
+use ast::expression::Expression;
+use ast::evaluable::{Value, Evaluable};
+use alphabet::Alphabet;
+use error::EvalError;
+
+/// A rule a combination breaks, along with what every part of the rule
+/// came to for that combination.
+pub struct Failure {
+    /// The rule's id, as returned by `Passcode::add_rule`.
+    pub id: usize,
+    /// The rule as it was given.
+    pub rule: String,
+    pub annotated: Annotated,
+}
+
+/// A part of a rule, written out, with the value it came to for a
+/// combination and the parts it's made of in turn.
+pub struct Annotated {
+    pub text: String,
+    /// The value the part came to, or why it couldn't be worked out,
+    /// e.g. because it reads a position the combination doesn't have.
+    pub value: Result<String, EvalError>,
+    pub parts: Vec<Annotated>,
+}
+
+impl Annotated {
+    /// Evaluates every part of `expression` for `combination`.
+    pub fn new(expression: &Expression, combination: &[u8], alphabet: &Alphabet) -> Annotated {
+        let parts = match *expression {
+            Expression::BinaryExp(ref bin_exp) => vec![
+                Annotated::new(&bin_exp.l_value, combination, alphabet),
+                Annotated::new(&bin_exp.r_value, combination, alphabet),
+            ],
+            Expression::Not(ref inner) => vec![Annotated::new(inner, combination, alphabet)],
+            _ => Vec::new(),
+        };
+        let value = expression.evaluate(combination).map(|value| match value {
+            Value::Numerical(n) => n.to_string(),
+            Value::Boolean(b) => b.to_string(),
+        });
+        Annotated { text: expression.render(alphabet), value, parts }
+    }
+
+    fn write(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        let indent = "  ".repeat(depth);
+        match self.value {
+            // A number written in the rule is its own value.
+            Ok(ref value) if *value == self.text => writeln!(f, "{}{}", indent, self.text)?,
+            Ok(ref value) => writeln!(f, "{}{} is {}", indent, self.text, value)?,
+            Err(ref error) => writeln!(f, "{}{}: {}", indent, self.text, error)?,
+        }
+        for part in self.parts.iter() {
+            part.write(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Prints each part on a line of its own with its value, indented under
+/// the part it belongs to:
+///
+/// ```text
+/// a + b = 5 is false
+///   a + b is 3
+///     a is 1
+///     b is 2
+///   5
+/// ```
+impl fmt::Display for Annotated {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast::parse_code;
+
+    fn annotate(rule: &str, combination: &[u8]) -> Annotated {
+        Annotated::new(&parse_code(rule, 4), combination, &Alphabet::decimal())
+    }
+
+    #[test]
+    fn values_of_every_part() {
+        let annotated = annotate("a + b = 5", &[1, 2, 3]);
+        assert_eq!(annotated.value, Ok("false".to_owned()));
+        assert_eq!(annotated.parts[0].text, "a + b");
+        assert_eq!(annotated.parts[0].value, Ok("3".to_owned()));
+        assert_eq!(annotated.to_string(), "a + b = 5 is false\n  a + b is 3\n    a is 1\n    b is 2\n  5\n");
+    }
+
+    #[test]
+    fn missing_positions() {
+        let annotated = annotate("d > len", &[4, 0]);
+        assert!(annotated.value.is_err());
+        assert_eq!(annotated.parts[0].text, "d");
+        assert!(annotated.parts[0].value.is_err());
+        assert_eq!(annotated.parts[1].value, Ok("2".to_owned()));
+    }
+}
@@ -0,0 +1,250 @@
+use ast::expression::Expression;
+use search::Search;
+
+/// The solutions of a passcode split into groups of positions that no
+/// rule links together, so the solutions as a whole are every way of
+/// picking one solution from each group. Counting and sampling them
+/// never goes through that product.
+pub struct Factored {
+    length: usize,
+    components: Vec<Component>,
+}
+
+/// A group of positions linked together by rules, along with every way
+/// of filling them in that satisfies those rules.
+pub struct Component {
+    positions: Vec<usize>,
+    solutions: Vec<Vec<u8>>,
+}
+
+impl Component {
+    /// The positions in the group, in increasing order.
+    pub fn positions(&self) -> &[usize] {
+        &self.positions
+    }
+
+    /// The values of the group's positions in each of its solutions.
+    pub fn solutions(&self) -> &[Vec<u8>] {
+        &self.solutions
+    }
+}
+
+impl Factored {
+    /// Splits up and solves a passcode of a fixed length, or returns
+    /// `None` if a rule needs the whole combination, like ones using
+    /// `len`, `d[-1]` or predicates do, since that links every position.
+    pub fn new(domains: &[Vec<u8>], rules: &[Expression]) -> Option<Factored> {
+        let length = domains.len();
+        let mut scopes: Vec<Vec<usize>> = Vec::with_capacity(rules.len());
+        for rule in rules.iter() {
+            let scope = rule.positions()?;
+            if scope.iter().any(|p| *p >= length) {
+                return None;
+            }
+            scopes.push(scope);
+        }
+
+        let mut parents: Vec<usize> = (0..length).collect();
+        for scope in scopes.iter() {
+            for pair in scope.windows(2) {
+                let (a, b) = (root(&mut parents, pair[0]), root(&mut parents, pair[1]));
+                parents[a.max(b)] = a.min(b);
+            }
+        }
+
+        let mut components: Vec<Component> = Vec::new();
+        for position in 0..length {
+            if root(&mut parents, position) != position {
+                continue;
+            }
+            let positions: Vec<usize> = (0..length).filter(|p| root(&mut parents, *p) == position).collect();
+            let component_rules: Vec<Expression> = rules.iter().zip(scopes.iter())
+                .filter(|&(_, scope)| scope.first().is_some_and(|p| positions.contains(p)))
+                .map(|(rule, _)| rule.clone())
+                .collect();
+
+            // Every other position is pinned to a single value, so the
+            // search only branches on the positions of this group.
+            let pinned: Vec<Vec<u8>> = domains.iter().enumerate().map(|(p, domain)| {
+                if positions.contains(&p) { domain.clone() } else { domain.iter().take(1).cloned().collect() }
+            }).collect();
+            let solutions = Search::new(&pinned, length, &component_rules)
+                .map(|combination| positions.iter().map(|p| combination[*p]).collect())
+                .collect();
+            components.push(Component { positions, solutions });
+        }
+
+        // Rules that read no positions at all belong to no group, but
+        // can still rule everything out.
+        let constant: Vec<Expression> = rules.iter().zip(scopes.iter()).filter(|&(_, scope)| scope.is_empty()).map(|(rule, _)| rule.clone()).collect();
+        if !constant.iter().all(|rule| rule.holds(&[])) {
+            for component in components.iter_mut() {
+                component.solutions.clear();
+            }
+        }
+        Some(Factored { length, components })
+    }
+
+    pub fn components(&self) -> &[Component] {
+        &self.components
+    }
+
+    /// The number of solutions, which can be far more than could ever
+    /// be enumerated.
+    pub fn count(&self) -> u128 {
+        self.components.iter().fold(1u128, |count, c| count.saturating_mul(c.solutions.len() as u128))
+    }
+
+    /// Every solution, by picking a solution for each group in turn
+    /// like an odometer, the last group turning fastest.
+    pub fn iter(&self) -> Product<'_> {
+        let done = self.components.iter().any(|c| c.solutions.is_empty());
+        Product { factored: self, choices: vec![0; self.components.len()], done }
+    }
+
+    /// Picks `count` solutions uniformly at random, by picking each
+    /// group's solution uniformly at random. The same seed always picks
+    /// the same solutions.
+    pub fn sample(&self, count: usize, seed: u64) -> Vec<Vec<u8>> {
+        if self.components.iter().any(|c| c.solutions.is_empty()) {
+            return Vec::new();
+        }
+        let mut rng = XorShift::new(seed);
+        (0..count).map(|_| {
+            let choices: Vec<usize> = self.components.iter().map(|c| rng.below(c.solutions.len())).collect();
+            self.combine(&choices)
+        }).collect()
+    }
+
+    fn combine(&self, choices: &[usize]) -> Vec<u8> {
+        let mut combination = vec![0; self.length];
+        for (component, &choice) in self.components.iter().zip(choices.iter()) {
+            for (position, value) in component.positions.iter().zip(component.solutions[choice].iter()) {
+                combination[*position] = *value;
+            }
+        }
+        combination
+    }
+}
+
+fn root(parents: &mut [usize], position: usize) -> usize {
+    let mut root = position;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    parents[position] = root;
+    root
+}
+
+pub struct Product<'a> {
+    factored: &'a Factored,
+    choices: Vec<usize>,
+    done: bool,
+}
+
+impl<'a> Iterator for Product<'a> {
+    type Item = Vec<u8>;
+    fn next(&mut self) -> Option<Vec<u8>> {
+        if self.done {
+            return None;
+        }
+        let combination = self.factored.combine(&self.choices);
+        self.done = true;
+        for (i, component) in self.factored.components.iter().enumerate().rev() {
+            self.choices[i] += 1;
+            if self.choices[i] < component.solutions.len() {
+                self.done = false;
+                break;
+            }
+            self.choices[i] = 0;
+        }
+        Some(combination)
+    }
+}
+
+/// A small xorshift generator, good enough to sample solutions with.
+struct XorShift {
+    state: u64,
+}
+
+impl XorShift {
+    fn new(seed: u64) -> XorShift {
+        // The state must never be zero.
+        XorShift { state: (seed ^ 0x9E37_79B9_7F4A_7C15) | 1 }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// A number below `n`, which must not be zero, each as likely as
+    /// the others. Draws that would favour the smaller numbers, past the
+    /// last whole multiple of `n`, are thrown away.
+    fn below(&mut self, n: usize) -> usize {
+        let n = n as u64;
+        let limit = u64::MAX - u64::MAX % n;
+        loop {
+            let draw = self.next();
+            if draw < limit {
+                return (draw % n) as usize;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast::parse_code;
+
+    fn factor(rules: &[&str], length: usize) -> Option<Factored> {
+        let rules: Vec<Expression> = rules.iter().map(|rule| parse_code(rule, length)).collect();
+        Factored::new(&vec![(0..10).collect(); length], &rules)
+    }
+
+    #[test]
+    fn splits_unrelated_positions() {
+        let factored = factor(&["a + b = 9", "c > d", "e = 4"], 6).unwrap();
+        let positions: Vec<&[usize]> = factored.components().iter().map(|c| c.positions()).collect();
+        assert_eq!(positions, vec![&[0, 1][..], &[2, 3][..], &[4][..], &[5][..]]);
+        assert_eq!(factored.count(), 10 * 45 * 10);
+        assert_eq!(factored.iter().count(), 10 * 45 * 10);
+        assert_eq!(factored.iter().nth(1), Some(vec![0, 9, 1, 0, 4, 1]));
+    }
+
+    #[test]
+    fn matches_enumeration() {
+        let rules = ["a * b = 12", "c % 3 = 1", "a < c"];
+        let factored = factor(&rules, 3).unwrap();
+        let mut found: Vec<Vec<u8>> = factored.iter().collect();
+        found.sort();
+        let rules: Vec<Expression> = rules.iter().map(|rule| parse_code(rule, 3)).collect();
+        let expected: Vec<Vec<u8>> = Search::new(&vec![(0..10).collect(); 3], 3, &rules).collect();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn counts_beyond_enumeration() {
+        let factored = factor(&["a = b", "c = 2"], 26).unwrap();
+        assert_eq!(factored.count(), 10u128.pow(24));
+    }
+
+    #[test]
+    fn samples_solutions() {
+        let factored = factor(&["a + b = 9", "c > 7"], 3).unwrap();
+        let samples = factored.sample(20, 7);
+        assert_eq!(samples.len(), 20);
+        assert!(samples.iter().all(|s| s[0] + s[1] == 9 && s[2] > 7));
+        assert_eq!(samples, factored.sample(20, 7));
+    }
+
+    #[test]
+    fn contradictions_and_whole_combination_rules() {
+        assert_eq!(factor(&["1 = 2"], 2).unwrap().count(), 0);
+        assert_eq!(factor(&["a > 9"], 2).unwrap().iter().count(), 0);
+        assert!(factor(&["len = 2"], 2).is_none());
+    }
+}
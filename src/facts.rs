@@ -0,0 +1,129 @@
+use ast::evaluable::{Value, Evaluable};
+use ast::predicate;
+use alphabet::Alphabet;
+use lock::Lock;
+
+/// What every candidate still possible has in common.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Facts {
+    /// The values each position holds across the candidates, in
+    /// increasing order. A position no candidate is long enough to have
+    /// holds none.
+    pub domains: Vec<Vec<u8>>,
+    /// The positions every candidate has with the same value, along
+    /// with that value.
+    pub fixed: Vec<(usize, u8)>,
+    /// Pairs of positions that hold the same value as each other in
+    /// every candidate, without always holding the same value. Each
+    /// position is paired with the first position it's equal to.
+    pub equal: Vec<(usize, usize)>,
+    /// The properties of the whole combination that hold for every
+    /// candidate.
+    pub properties: Vec<Property>,
+}
+
+/// A property of a whole combination.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Property {
+    /// No value appears twice.
+    Distinct,
+    /// Every value is greater than the one before it.
+    Increasing,
+    /// Every value is less than the one before it.
+    Decreasing,
+    /// A true or false predicate of the lock, written the way rules use
+    /// it, such as `pressed(3)`.
+    Predicate(String),
+}
+
+impl Facts {
+    /// Goes through `candidates`, none of which are longer than
+    /// `max_length`, checking the properties that make sense for
+    /// `lock`. Returns `None` if there are no candidates.
+    pub fn gather<I>(candidates: I, max_length: usize, lock: &Lock, alphabet: &Alphabet) -> Option<Facts>
+        where I: Iterator<Item = Vec<u8>>
+    {
+        let ordered = match *lock {
+            Lock::Code | Lock::Dial { .. } => vec![Property::Distinct, Property::Increasing, Property::Decreasing],
+            Lock::PushButton { .. } | Lock::Pattern => Vec::new(),
+        };
+        let predicates = predicate::library(lock, alphabet);
+        let mut holds = vec![true; ordered.len() + predicates.len()];
+        let mut seen = vec![[0u64; 4]; max_length];
+        let mut first: Option<Vec<u8>> = None;
+        let mut shortest = max_length;
+        // Whether positions `i` and `j` have held the same value so far,
+        // for `j` below `i`.
+        let mut same: Vec<Vec<bool>> = (0..max_length).map(|i| vec![true; i]).collect();
+        for candidate in candidates {
+            for (position, value) in candidate.iter().enumerate() {
+                seen[position][*value as usize / 64] |= 1 << (*value % 64);
+                for (j, equal) in same[position].iter_mut().enumerate() {
+                    *equal = *equal && candidate[j] == *value;
+                }
+            }
+            shortest = shortest.min(candidate.len());
+            for (property, holds) in ordered.iter().zip(holds.iter_mut()) {
+                *holds = *holds && candidate.len() > 1 && has(property, &candidate);
+            }
+            for (predicate, holds) in predicates.iter().zip(holds[ordered.len()..].iter_mut()) {
+                *holds = *holds && predicate.evaluate(&candidate) == Ok(Value::Boolean(true));
+            }
+            if first.is_none() {
+                first = Some(candidate);
+            }
+        }
+        let first = first?;
+        let domains: Vec<Vec<u8>> = seen.iter().map(|words| {
+            (0..=255u8).filter(|v| words[*v as usize / 64] & (1 << (*v % 64)) != 0).collect()
+        }).collect();
+        let fixed: Vec<(usize, u8)> = (0..shortest).filter(|i| domains[*i].len() == 1).map(|i| (i, first[i])).collect();
+        let equal: Vec<(usize, usize)> = (0..shortest).filter(|i| domains[*i].len() > 1).filter_map(|i| {
+            same[i].iter().position(|equal| *equal).map(|j| (j, i))
+        }).collect();
+        let (holds_ordered, holds_predicates) = holds.split_at(ordered.len());
+        let mut properties: Vec<Property> = ordered.into_iter().zip(holds_ordered).filter(|&(_, holds)| *holds).map(|(property, _)| property).collect();
+        properties.extend(predicates.iter().zip(holds_predicates).filter(|&(_, holds)| *holds).map(|(predicate, _)| {
+            Property::Predicate(predicate.render(alphabet))
+        }));
+        Some(Facts { domains, fixed, equal, properties })
+    }
+}
+
+fn has(property: &Property, combination: &[u8]) -> bool {
+    match *property {
+        Property::Distinct => (1..combination.len()).all(|i| !combination[..i].contains(&combination[i])),
+        Property::Increasing => combination.windows(2).all(|pair| pair[0] < pair[1]),
+        Property::Decreasing => combination.windows(2).all(|pair| pair[0] > pair[1]),
+        Property::Predicate(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn what_every_candidate_shares() {
+        let candidates = vec![vec![1, 4, 7, 4], vec![2, 5, 7, 5], vec![3, 6, 7, 6, 9]];
+        let facts = Facts::gather(candidates.into_iter(), 5, &Lock::Code, &Alphabet::decimal()).unwrap();
+        assert_eq!(facts.domains, vec![vec![1, 2, 3], vec![4, 5, 6], vec![7], vec![4, 5, 6], vec![9]]);
+        assert_eq!(facts.fixed, vec![(2, 7)]);
+        assert_eq!(facts.equal, vec![(1, 3)]);
+        assert_eq!(facts.properties, Vec::new());
+
+        let candidates = vec![vec![1, 2, 3], vec![2, 5, 8]];
+        let facts = Facts::gather(candidates.into_iter(), 3, &Lock::Code, &Alphabet::decimal()).unwrap();
+        assert_eq!(facts.properties, vec![Property::Distinct, Property::Increasing]);
+        assert!(Facts::gather(Vec::new().into_iter(), 3, &Lock::Code, &Alphabet::decimal()).is_none());
+    }
+
+    #[test]
+    fn predicates_of_the_lock() {
+        let buttons = Alphabet::from_chars("123").unwrap();
+        let candidates = vec![vec![1, 0, 1], vec![1, 2, 1]];
+        let facts = Facts::gather(candidates.into_iter(), 3, &Lock::PushButton { ordered: true }, &buttons).unwrap();
+        let rendered = vec!["pressed(1)", "pressed(3)", "together(1, 3)"];
+        assert_eq!(facts.properties, rendered.into_iter().map(|p| Property::Predicate(p.to_owned())).collect::<Vec<Property>>());
+    }
+}
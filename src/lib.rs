@@ -3,162 +3,1217 @@
 extern crate test;
 
 mod ast;
+mod alphabet;
+mod mask;
+mod lock;
+mod space;
+mod candidate_set;
+mod search;
+mod propagate;
+mod projection;
+mod factored;
+mod batch;
+mod explain;
+mod error;
+mod facts;
+mod report;
+mod suggest;
+pub mod strategy;
 
+#[cfg(test)]
 use test::Bencher;
 
+use std::ops::{Range, RangeInclusive};
+use std::cell::Cell;
+use std::thread;
+
+pub use alphabet::Alphabet;
+pub use mask::Mask;
+pub use lock::Lock;
+pub use factored::Factored;
+pub use explain::{Failure, Annotated};
+pub use facts::{Facts, Property};
+pub use report::Report;
+pub use suggest::Query;
+pub use strategy::{Feedback, Strategy, Outlook};
+pub use error::{Error, RuleError, EvalError, AlphabetError, MaskError, CombinationError};
 use ast::expression::Expression;
-use ast::evaluable::{Value, Evaluable};
+use ast::predicate::{self, Predicate};
+use ast::bytecode::Program;
+use space::Space;
+use candidate_set::{CandidateSet, Candidates};
+use search::Search;
+use projection::{Projection, Check};
+use batch::{Batch, BATCH_SIZE};
+#[cfg(test)]
+use space::CombinationGenerator;
+
+/// The most positions a passcode can have, as the length of each
+/// candidate is kept in a byte while rules are checked.
+pub const MAX_LENGTH: usize = u8::MAX as usize;
+
+/// The most candidates `eliminate_combinations` goes through, as it
+/// keeps a bit for each of them. Longer passcodes can still be solved
+/// with `search` or `factor`.
+pub const MAX_CANDIDATES: u64 = 1 << 30;
+
+/// How many of the candidates left `SolveState::Ambiguous` gives.
+const SAMPLE_SIZE: usize = 5;
+
+/// How many of the candidates left are tried as the next guess, and
+/// scored against each other, which bounds the work of picking a guess.
+/// When more are left, they're sampled evenly.
+const GUESS_SAMPLE: usize = 2000;
 
+/// How many of the candidates left questions are tried out on before
+/// being suggested. When more are left, they're sampled evenly.
+const QUERY_SAMPLE: usize = 10_000;
+
+/// Where the search stands with the rules given so far.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SolveState {
+    /// No combination satisfies every rule, so some rule must be wrong.
+    Contradiction,
+    /// Exactly one combination is left.
+    Unique(Vec<u8>),
+    /// `count` combinations are left, the first few of which are in
+    /// `sample`.
+    Ambiguous { count: usize, sample: Vec<Vec<u8>> },
+}
+
+/// The rules given so far, each parsed into an expression tree and
+/// compiled into a program, which is what candidates are checked with.
+/// Every rule gets an id of its own, which stays the same as other rules
+/// are taken back.
 struct RuleList {
-    rules: Vec<Box<Expression>>,
+    rules: Vec<Expression>,
+    programs: Vec<Program>,
+    ids: Vec<usize>,
+    texts: Vec<String>,
+    next_id: usize,
 }
 
 impl RuleList {
     fn new() -> RuleList {
-        RuleList { rules: Vec::new() }
+        RuleList { rules: Vec::new(), programs: Vec::new(), ids: Vec::new(), texts: Vec::new(), next_id: 0 }
+    }
+
+    fn add_rule(&mut self, rule: &str, context: &ast::Context) -> Result<usize, Error> {
+        let ast = ast::convert_string_to_ast(rule, context).map_err(|error| Error::Rule { rule: rule.to_owned(), error })?;
+        Ok(self.push(rule, *ast))
+    }
+
+    fn push(&mut self, text: &str, rule: Expression) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.insert(id, text.trim().to_owned(), rule);
+        id
+    }
+
+    fn insert(&mut self, id: usize, text: String, rule: Expression) {
+        self.programs.push(Program::compile(&rule));
+        self.rules.push(rule);
+        self.ids.push(id);
+        self.texts.push(text);
+    }
+
+    fn position(&self, id: usize) -> Option<usize> {
+        self.ids.iter().position(|i| *i == id)
+    }
+
+    fn remove(&mut self, position: usize) -> (String, Expression) {
+        self.programs.remove(position);
+        self.ids.remove(position);
+        (self.texts.remove(position), self.rules.remove(position))
     }
 
-    fn add_rule(&mut self, rule: &str, length: u8) -> Result<(), String> {
-        let ast = ast::convert_string_to_ast(rule, length)?;
-        self.rules.push(ast);
-        Ok(())
+    /// Checks a combination against every rule. A rule that can't be
+    /// evaluated, e.g. because it refers to a position the combination
+    /// is too short to have, counts as broken.
+    fn run_rules(&self, combination: &[u8]) -> bool {
+        self.programs.iter().all(|program| program.holds(combination))
     }
 
-    fn run_rules(&self, combination: &Vec<u8>) -> bool {
-        for rule in self.rules.iter() {
-            if let Ok(Value::Boolean(res)) = rule.evaluate(combination) {
-                if !res { return false; }
+    /// Prepares the rules in `range` for checking the candidates of a
+    /// space, each along with the tag of the candidates it removes.
+    /// Rules that only read a few positions are tabulated over those
+    /// positions whenever that takes fewer evaluations than the space
+    /// has candidates, and go first since looking them up is cheap.
+    fn checks(&self, space: &Space, range: Range<usize>) -> Vec<(u32, Check<'_>)> {
+        let radix = space.max_value() as usize + 1;
+        let limit = space.size().map_or(PROJECTION_LIMIT, |size| size.min(PROJECTION_LIMIT as u64) as usize);
+        let rules = self.rules[range.clone()].iter().zip(self.programs[range.clone()].iter()).zip(self.ids[range].iter());
+        let mut checks: Vec<(u32, Check)> = rules.map(|((rule, program), id)| (tag(*id), match Projection::new(rule, radix, limit) {
+            Some(projection) => Check::Lookup(projection),
+            None => Check::Run(program),
+        })).collect();
+        checks.sort_by_key(|(_, check)| match *check {
+            Check::Lookup(_) => 0,
+            Check::Run(_) => 1,
+        });
+        checks
+    }
+}
+
+/// The tag the candidates a rule removes are recorded with.
+fn tag(id: usize) -> u32 {
+    id as u32 + 1
+}
+
+/// Tags each candidate of a batch with the first of the checks it
+/// fails, leaving the ones that pass every check untagged.
+fn verdict<'a>(space: &'a Space, checks: &'a [(u32, Check<'a>)]) -> impl FnMut(&[u64], &mut [u32]) + 'a {
+    let mut batch = Batch::new(space.max_length());
+    let mut keep: Vec<bool> = Vec::new();
+    move |indices: &[u64], tags: &mut [u32]| {
+        space.decode_batch(indices, &mut batch);
+        keep.clear();
+        keep.resize(indices.len(), true);
+        for &(tag, ref check) in checks.iter() {
+            check.holds_batch(&batch, &mut keep);
+            for (t, kept) in tags.iter_mut().zip(keep.iter()) {
+                if !kept && *t == 0 {
+                    *t = tag;
+                }
             }
         }
-        true
     }
 }
 
+/// The name rules use for a position: a letter for the first 26, and
+/// an index after that.
+pub fn position_name(i: usize) -> String {
+    if i < 26 { ((b'a' + i as u8) as char).to_string() } else { format!("d[{}]", i + 1) }
+}
+
+/// Fails if a passcode can't have `length` positions.
+fn check_length(length: usize) -> Result<(), Error> {
+    if length > MAX_LENGTH {
+        return Err(Error::TooManyPositions(length));
+    }
+    Ok(())
+}
+
+/// A rule being added or taken back, kept so it can be undone.
+struct Edit {
+    added: bool,
+    id: usize,
+    text: String,
+    rule: Expression,
+}
+
+/// The most entries a rule's projection table may have.
+const PROJECTION_LIMIT: usize = 1 << 20;
+
 /// The Passcode struct is used to store all of the data involved in an
 /// attempt to solve a given passcode:
-/// * the length of the passcode
-/// * all of the possible solutions
-/// * the rules given by the user
+/// * the kind of lock the passcode is for
+/// * the lengths the passcode may have
+/// * the alphabet of symbols the passcode is made of
+/// * the symbols allowed at each position
+/// * every candidate the passcode started out with
+/// * which of those candidates are still possible
+/// * the rules given by the user, and the changes made to them
 pub struct Passcode {
-    length: usize,
-    possible_values: Vec<Vec<u8>>,
+    lock: Lock,
+    min_length: usize,
+    alphabet: Alphabet,
+    domains: Vec<Vec<u8>>,
+    /// The domains and shortest length before any rule narrowed them
+    /// down, to start over from when rules are taken back.
+    base_domains: Vec<Vec<u8>>,
+    base_min_length: usize,
+    search_space: Option<u64>,
+    space: Space,
+    candidates: CandidateSet,
     rule_list: RuleList,
+    /// How many of the rules every candidate left has been checked
+    /// against.
+    applied_rules: usize,
+    history: Vec<Edit>,
+    undone: Vec<Edit>,
+    collapse: bool,
+    /// How many candidates are left once collapsed, worked out the first
+    /// time it's asked for after the candidates change.
+    collapsed: Cell<Option<usize>>,
+    threads: usize,
 }
 
 impl Passcode {
+    /// Creates a passcode of the given length where every position
+    /// can be any symbol of the alphabet. Fails if the length is over
+    /// `MAX_LENGTH`.
+    ///
+    /// ```
+    /// use passcode_cracker::{Passcode, Alphabet, Error};
     /// 
+    /// let passcode = Passcode::new(4, Alphabet::decimal()).unwrap();
+    /// assert_eq!(passcode.solutions_left(), 10000);
+    /// assert!(matches!(Passcode::new(300, Alphabet::decimal()), Err(Error::TooManyPositions(300))));
+    /// ```
+    pub fn new(length: usize, alphabet: Alphabet) -> Result<Passcode, Error> {
+        check_length(length)?;
+        let domains = vec![alphabet.values().to_vec(); length];
+        Ok(Passcode::with_domains(alphabet, domains, length))
+    }
+
+    /// Creates a passcode whose length isn't known exactly. Candidates
+    /// of every length in the range are tracked together, shortest
+    /// first. Rules can use `len` for the length of a candidate and
+    /// `d[-1]`, `d[-2]`, ... for positions counted from its end. A rule
+    /// that refers to a position a candidate doesn't have eliminates
+    /// that candidate. Fails if the longest length is over `MAX_LENGTH`.
+    ///
+    /// ```
+    /// use passcode_cracker::{Passcode, Alphabet};
+    ///
+    /// let mut passcode = Passcode::with_lengths(1..=3, Alphabet::decimal()).unwrap();
+    /// assert_eq!(passcode.solutions_left(), 10 + 100 + 1000);
+    /// passcode.add_rule("d[-1] = 7").unwrap();
+    /// passcode.add_rule("c > 4").unwrap();
+    /// passcode.eliminate_combinations().unwrap();
+    /// assert_eq!(passcode.solutions_left(), 100);
+    /// ```
+    pub fn with_lengths(lengths: RangeInclusive<usize>, alphabet: Alphabet) -> Result<Passcode, Error> {
+        check_length(*lengths.end())?;
+        let domains = vec![alphabet.values().to_vec(); *lengths.end()];
+        Ok(Passcode::with_domains(alphabet, domains, *lengths.start()))
+    }
+
+    /// Creates a passcode whose positions are restricted up front by a
+    /// hashcat style mask (see `Mask`), so only the combinations the
+    /// mask allows are ever generated.
+    ///
     /// ```
     /// use passcode_cracker::Passcode;
-    /// 
-    /// let passcode = Passcode::new(4);
+    ///
+    /// let passcode = Passcode::with_mask("?d?d[0-3]?l").unwrap();
+    /// assert_eq!(passcode.search_space(), Some(10 * 10 * 4 * 26));
+    /// assert_eq!(passcode.solutions_left(), 10 * 10 * 4 * 26);
+    /// ```
+    pub fn with_mask(mask: &str) -> Result<Passcode, Error> {
+        let mask = Mask::parse(mask)?;
+        Passcode::from_mask(mask)
+    }
+
+    /// Creates a passcode out of an already parsed mask. `Mask::size`
+    /// can be used beforehand to check how many combinations this will
+    /// generate. Fails if the mask has more than `MAX_LENGTH` positions.
+    pub fn from_mask(mask: Mask) -> Result<Passcode, Error> {
+        check_length(mask.len())?;
+        let domains = mask.domains().to_vec();
+        let length = domains.len();
+        Ok(Passcode::with_domains(mask.alphabet().clone(), domains, length))
+    }
+
+    /// Creates a passcode for a push-button lock where the order the
+    /// buttons are pressed in matters, and buttons may be pressed
+    /// together, like a Simplex lock. Each button can be used at most
+    /// once. Rules can use `pressed(x)`, `together(x, y)`, `step(x)`
+    /// and `count`, and negate a predicate with `!`. Every combination
+    /// is listed up front, so this fails for more than 8 buttons.
+    ///
+    /// ```
+    /// use passcode_cracker::{Passcode, Alphabet, Error};
+    ///
+    /// let mut passcode = Passcode::button_sequence(Alphabet::from_chars("12345").unwrap()).unwrap();
+    /// assert_eq!(passcode.solutions_left(), 1082);
+    /// passcode.add_rule("count = 2").unwrap();
+    /// passcode.add_rule("together(1, 4)").unwrap();
+    /// passcode.eliminate_combinations().unwrap();
+    /// assert_eq!(passcode.format(&passcode.solution().unwrap()), "1+4");
+    /// let buttons = Alphabet::from_chars("0123456789").unwrap();
+    /// assert_eq!(Passcode::button_sequence(buttons).err(), Some(Error::TooManyButtons { buttons: 10, most: 8 }));
     /// ```
-    pub fn new(length: usize) -> Passcode {
-        let combinations: Vec<Vec<u8>> = CombinationGenerator::new(length).collect();
+    pub fn button_sequence(buttons: Alphabet) -> Result<Passcode, Error> {
+        Passcode::push_button(buttons, true)
+    }
 
+    /// Creates a passcode for a push-button lock where only the set of
+    /// pressed buttons matters, not their order. This fails for more
+    /// than 20 buttons.
+    ///
+    /// ```
+    /// use passcode_cracker::{Passcode, Alphabet};
+    ///
+    /// let mut passcode = Passcode::button_set(Alphabet::from_chars("12345").unwrap()).unwrap();
+    /// assert_eq!(passcode.solutions_left(), 32);
+    /// passcode.add_rule("pressed(3)").unwrap();
+    /// passcode.add_rule("!pressed(5)").unwrap();
+    /// passcode.eliminate_combinations().unwrap();
+    /// assert_eq!(passcode.solutions_left(), 8);
+    /// ```
+    pub fn button_set(buttons: Alphabet) -> Result<Passcode, Error> {
+        Passcode::push_button(buttons, false)
+    }
+
+    /// Creates a passcode for a rotary dial padlock, where the code is
+    /// `length` numbers from the dial. The dial's numbers are the
+    /// alphabet, whose values must run from 0 up to one less than its
+    /// size, such as `Alphabet::dial(40)`. Arithmetic in rules wraps
+    /// around the dial, and `%` can be used for relations like "the
+    /// first and third numbers are congruent mod 4". The lock opens for
+    /// any number within `tolerance` of the real one.
+    ///
+    /// ```
+    /// use passcode_cracker::{Passcode, Alphabet};
+    ///
+    /// let mut passcode = Passcode::dial(3, Alphabet::dial(40).unwrap(), 1).unwrap();
+    /// assert_eq!(passcode.solutions_left(), 64000);
+    /// passcode.add_rule("a + 5 = 2").unwrap();
+    /// passcode.eliminate_combinations().unwrap();
+    /// assert_eq!(passcode.format(&passcode.candidates().next().unwrap()), "37-0-0");
+    /// ```
+    pub fn dial(length: usize, alphabet: Alphabet, tolerance: u8) -> Result<Passcode, Error> {
+        check_length(length)?;
+        let size = alphabet.len();
+        let mut values = alphabet.values().to_vec();
+        values.sort_unstable();
+        if values.iter().enumerate().any(|(i, v)| *v as usize != i) {
+            return Err(Error::DialValues { size });
+        }
+        let domains = vec![alphabet.values().to_vec(); length];
+        let mut passcode = Passcode::with_domains(alphabet, domains, length);
+        passcode.lock = Lock::Dial { size, tolerance };
+        Ok(passcode)
+    }
+
+    /// Creates a passcode for an Android style unlock pattern: 4 to 9
+    /// dots on a 3x3 grid numbered like a keypad, never reusing a dot
+    /// or jumping over one that hasn't been visited yet. Positions hold
+    /// the dots in the order they're visited, and rules can also use
+    /// `starts_at_corner`, `crosses` and `turns`.
+    ///
+    /// ```
+    /// use passcode_cracker::Passcode;
+    ///
+    /// let mut passcode = Passcode::pattern();
+    /// assert_eq!(passcode.solutions_left(), 389112);
+    /// passcode.add_rule("len = 4").unwrap();
+    /// passcode.add_rule("starts_at_corner").unwrap();
+    /// passcode.add_rule("turns = 1").unwrap();
+    /// passcode.eliminate_combinations().unwrap();
+    /// assert_eq!(passcode.solutions_left(), 64);
+    /// ```
+    pub fn pattern() -> Passcode {
+        let dots = Alphabet::with_values(&[("1", 1), ("2", 2), ("3", 3), ("4", 4), ("5", 5), ("6", 6), ("7", 7), ("8", 8), ("9", 9)]).unwrap();
+        let domains = vec![(1..=9).collect(); 9];
+        let space = Space::table(&Lock::pattern_combinations());
+        Passcode::build(Lock::Pattern, dots, domains, 4, space)
+    }
+
+    fn push_button(buttons: Alphabet, ordered: bool) -> Result<Passcode, Error> {
+        let length = buttons.len();
+        let most = Lock::most_buttons(ordered);
+        if length > most {
+            return Err(Error::TooManyButtons { buttons: length, most });
+        }
+        let last_step = if ordered { length as u8 } else { 1 };
+        let domains = vec![(0..=last_step).collect(); length];
+        let space = Space::table(&Lock::push_button_combinations(length, ordered));
+        Ok(Passcode::build(Lock::PushButton { ordered }, buttons, domains, length, space))
+    }
+
+    fn with_domains(alphabet: Alphabet, domains: Vec<Vec<u8>>, min_length: usize) -> Passcode {
+        let space = Space::domains(&domains, min_length);
+        Passcode::build(Lock::Code, alphabet, domains, min_length, space)
+    }
+
+    fn build(lock: Lock, alphabet: Alphabet, domains: Vec<Vec<u8>>, min_length: usize, space: Space) -> Passcode {
         Passcode {
-            length: length,
-            possible_values: combinations,
+            lock,
+            min_length,
+            alphabet,
+            base_domains: domains.clone(),
+            base_min_length: min_length,
+            domains,
+            search_space: space.size(),
+            candidates: CandidateSet::full(space.size().unwrap_or(u64::MAX)),
+            space,
             rule_list: RuleList::new(),
+            applied_rules: 0,
+            history: Vec::new(),
+            undone: Vec::new(),
+            collapse: false,
+            collapsed: Cell::new(None),
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
         }
     }
 
     /// Runs through the list of possible solution against the list of
     /// supplied rules. All solutions that are proven invalid are
     /// removed from the struct. Returns the number of bad combinations
-    /// removed as a result of running.
+    /// removed as a result of running, or `Error::TooManyCandidates` if
+    /// there are more than `MAX_CANDIDATES` to go through even once the
+    /// rules have narrowed down the domains.
+    ///
+    /// The candidates left have already been checked against the rules
+    /// of earlier runs, so only rules added since are run, unless the
+    /// domains have been narrowed down so far that starting over from
+    /// them goes through fewer candidates than there are left.
     /// 
     /// ```
-    /// use passcode_cracker::Passcode;
+    /// use passcode_cracker::{Passcode, Alphabet, Error};
     /// 
-    /// let mut passcode = Passcode::new(2);
-    /// assert_eq!(passcode.eliminate_combinations(), 0);
+    /// let mut passcode = Passcode::new(2, Alphabet::decimal()).unwrap();
+    /// assert_eq!(passcode.eliminate_combinations(), Ok(0));
     /// passcode.add_rule("2 + 2 = a");
-    /// assert_eq!(passcode.eliminate_combinations(), 90);
+    /// assert_eq!(passcode.eliminate_combinations(), Ok(90));
+    /// let mut long = Passcode::new(12, Alphabet::decimal()).unwrap();
+    /// assert_eq!(long.eliminate_combinations(), Err(Error::TooManyCandidates(Some(10u64.pow(12)))));
     /// ```
     /// 
-    pub fn eliminate_combinations(&mut self) -> usize {
-        let mut bad_combinations = Vec::new();
-        for (i, combination) in self.possible_values.iter().enumerate() {
-            if !self.rule_list.run_rules(&combination) {
-                bad_combinations.push(i);
+    pub fn eliminate_combinations(&mut self) -> Result<usize, Error> {
+        let before = self.candidates.len();
+        self.propagate();
+        if let Space::Domains(_) = self.space {
+            let reduced = Space::domains(&self.domains, self.min_length);
+            if reduced.size().is_some_and(|size| size < before) {
+                self.start_over(reduced);
             }
         }
+        match self.space.size() {
+            Some(size) if size <= MAX_CANDIDATES => {},
+            size => return Err(Error::TooManyCandidates(size)),
+        }
+        self.apply_rules();
+        Ok((before - self.candidates.len()) as usize)
+    }
 
-        for bad_index in bad_combinations.iter().rev() {
-            self.possible_values.remove(*bad_index);
+    /// Replaces the candidates with every candidate of `space`, which no
+    /// rule has been checked against yet.
+    fn start_over(&mut self, space: Space) {
+        self.candidates = CandidateSet::full(space.size().unwrap_or(u64::MAX));
+        self.collapsed.set(None);
+        self.space = space;
+        self.applied_rules = 0;
+    }
+
+    /// Checks the candidates left against the rules added since they
+    /// were last checked.
+    fn apply_rules(&mut self) {
+        let space = &self.space;
+        let checks = self.rule_list.checks(space, self.applied_rules..self.rule_list.rules.len());
+        let tags: Vec<u32> = checks.iter().map(|&(tag, _)| tag).collect();
+        self.candidates.retain_batches(&tags, BATCH_SIZE, self.threads, || verdict(space, &checks));
+        self.collapsed.set(None);
+        self.applied_rules = self.rule_list.rules.len();
+    }
+
+    /// Narrows down the symbols each position can hold using the rules
+    /// alone, without going through any candidates. A rule over one or
+    /// two positions keeps only the symbols it holds for, so `a + b = 17`
+    /// leaves `a` and `b` with just 8 and 9, while longer rules narrow
+    /// down the range of values each side can take. The positions rules
+    /// refer to also raise the shortest length the passcode can have.
+    /// Returns whether anything was narrowed down.
+    ///
+    /// This also happens as part of `eliminate_combinations`, which then
+    /// only goes through the combinations of the narrowed down domains.
+    ///
+    /// ```
+    /// use passcode_cracker::{Passcode, Alphabet};
+    ///
+    /// let mut passcode = Passcode::new(3, Alphabet::decimal()).unwrap();
+    /// passcode.add_rule("a + b = 17").unwrap();
+    /// passcode.add_rule("c < a").unwrap();
+    /// assert!(passcode.propagate());
+    /// assert_eq!(passcode.domains()[0], vec![8, 9]);
+    /// assert_eq!(passcode.domains()[1], vec![8, 9]);
+    /// assert_eq!(passcode.domains()[2], vec![0, 1, 2, 3, 4, 5, 6, 7, 8]);
+    /// ```
+    pub fn propagate(&mut self) -> bool {
+        let mut narrowed = propagate::propagate(&mut self.domains, &self.rule_list.rules);
+        let needed = self.rule_list.rules.iter()
+            .filter_map(|rule| rule.positions())
+            .filter_map(|positions| positions.into_iter().max())
+            .map(|position| position + 1)
+            .max().unwrap_or(0);
+        if needed > self.min_length && needed <= self.domains.len() {
+            self.min_length = needed;
+            narrowed = true;
         }
+        narrowed
+    }
 
-        bad_combinations.len()
+    /// Sets how many threads `eliminate_combinations` splits the
+    /// candidates across, which starts out as the number of CPU cores.
+    /// The candidates left are the same whatever the number of threads.
+    /// Passcodes can also be shared between threads themselves.
+    ///
+    /// ```
+    /// use passcode_cracker::{Passcode, Alphabet};
+    /// use std::sync::{Arc, Mutex};
+    /// use std::thread;
+    ///
+    /// let mut passcode = Passcode::new(5, Alphabet::decimal()).unwrap();
+    /// passcode.use_threads(4);
+    /// passcode.add_rule("a + b + c + d + e = 40").unwrap();
+    /// let passcode = Arc::new(Mutex::new(passcode));
+    /// let shared = Arc::clone(&passcode);
+    /// thread::spawn(move || shared.lock().unwrap().eliminate_combinations()).join().unwrap();
+    /// assert_eq!(passcode.lock().unwrap().solutions_left(), 126);
+    /// ```
+    pub fn use_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
     }
 
     /// Adds a rule to the passcode, allowing it to remove more
     /// possible solutions as invalid. Accepts a &str formatted
     /// as a mathematical equation with at least one boolean operator.
+    /// Symbols of the alphabet can be used as values by quoting them.
+    /// Positions are single letters, `a` for the first. Letters run
+    /// together are read as one name, such as `len` or a predicate, so
+    /// `ab` is an unknown name rather than `a` next to `b`. Returns the
+    /// rule's id, which `remove_rule` takes.
     /// 
     /// ```
-    /// use passcode_cracker::Passcode;
+    /// use passcode_cracker::{Passcode, Alphabet};
     /// 
-    /// let mut passcode = Passcode::new(2);
-    /// let res = match passcode.add_rule("2 + 2 = a") {
+    /// let mut passcode = Passcode::new(2, Alphabet::hexadecimal()).unwrap();
+    /// let res = match passcode.add_rule("a = 'f'") {
     ///     Ok(_) => true,
     ///     Err(_) => false,
     /// };
+    /// assert!(res);
+    /// assert_eq!(passcode.eliminate_combinations(), Ok(240));
     /// ```
-    pub fn add_rule(&mut self, rule: &str) -> Result<(), String> {
-        self.rule_list.add_rule(rule, self.length as u8)
+    pub fn add_rule(&mut self, rule: &str) -> Result<usize, Error> {
+        let context = ast::Context { total_vars: self.domains.len(), alphabet: &self.alphabet, lock: &self.lock };
+        let id = self.rule_list.add_rule(rule, &context)?;
+        self.record_added(id);
+        Ok(id)
     }
 
-    pub fn print_solutions(&self) {
-        for c in self.possible_values.iter() {
-            println!("{:?}", c);
+    /// Adds several rules at once, so they can all be applied by a
+    /// single run of `eliminate_combinations`. If any of the rules can't
+    /// be parsed, none of them are added.
+    ///
+    /// ```
+    /// use passcode_cracker::{Passcode, Alphabet};
+    ///
+    /// let mut passcode = Passcode::new(4, Alphabet::decimal()).unwrap();
+    /// assert!(passcode.add_rules(&["a = 1", "b > 5", "c +"]).is_err());
+    /// passcode.add_rules(&["a = 1", "b > 5", "c = d"]).unwrap();
+    /// assert_eq!(passcode.eliminate_combinations(), Ok(10000 - 40));
+    /// passcode.add_rule("d % 2 = 0").unwrap();
+    /// assert_eq!(passcode.eliminate_combinations(), Ok(20));
+    /// ```
+    pub fn add_rules(&mut self, rules: &[&str]) -> Result<Vec<usize>, Error> {
+        let parsed = {
+            let context = ast::Context { total_vars: self.domains.len(), alphabet: &self.alphabet, lock: &self.lock };
+            let mut parsed: Vec<(String, Expression)> = Vec::with_capacity(rules.len());
+            for rule in rules.iter() {
+                match ast::convert_string_to_ast(rule, &context) {
+                    Ok(ast) => parsed.push((rule.to_string(), *ast)),
+                    Err(error) => return Err(Error::Rule { rule: rule.to_string(), error }),
+                }
+            }
+            parsed
+        };
+        Ok(self.push_rules(parsed))
+    }
+
+    fn push_rules(&mut self, rules: Vec<(String, Expression)>) -> Vec<usize> {
+        let ids = rules.into_iter().map(|(text, rule)| self.rule_list.push(&text, rule)).collect::<Vec<usize>>();
+        for id in ids.iter() {
+            self.record_added(*id);
         }
+        ids
     }
 
-    pub fn solutions_left(&self) -> usize {
-        self.possible_values.len()
+    fn record_added(&mut self, id: usize) {
+        let position = self.rule_list.position(id).unwrap();
+        let (text, rule) = (self.rule_list.texts[position].clone(), self.rule_list.rules[position].clone());
+        self.history.push(Edit { added: true, id, text, rule });
+        self.undone.clear();
     }
 
-    pub fn solution_exists(&self) -> bool {
-        self.solutions_left() > 0
+    /// The rules in place, along with their ids, in the order they were
+    /// added.
+    pub fn rules(&self) -> Vec<(usize, &str)> {
+        self.rule_list.ids.iter().cloned().zip(self.rule_list.texts.iter().map(|text| text.as_str())).collect()
+    }
+
+    /// Takes back a rule, such as a hint that turned out to be wrong.
+    /// Every candidate it ruled out that no other rule does is put back,
+    /// and the domains it narrowed down are widened again. Returns how
+    /// many candidates were put back.
+    ///
+    /// ```
+    /// use passcode_cracker::{Passcode, Alphabet};
+    ///
+    /// let mut passcode = Passcode::new(3, Alphabet::decimal()).unwrap();
+    /// let sum = passcode.add_rule("a + b = 9").unwrap();
+    /// passcode.add_rule("c = 4").unwrap();
+    /// let wrong = passcode.add_rule("a > 6").unwrap();
+    /// passcode.eliminate_combinations().unwrap();
+    /// assert_eq!(passcode.solutions_left(), 3);
+    /// assert_eq!(passcode.remove_rule(wrong), Ok(7));
+    /// assert_eq!(passcode.solutions_left(), 10);
+    /// passcode.remove_rule(sum).unwrap();
+    /// assert_eq!(passcode.solutions_left(), 100);
+    /// assert!(passcode.remove_rule(wrong).is_err());
+    /// ```
+    pub fn remove_rule(&mut self, id: usize) -> Result<usize, Error> {
+        let position = self.rule_list.position(id).ok_or(Error::NoSuchRule(id))?;
+        let before = self.candidates.len();
+        let (text, rule) = self.take_back(position);
+        self.history.push(Edit { added: false, id, text, rule });
+        self.undone.clear();
+        Ok(self.candidates.len().saturating_sub(before) as usize)
     }
 
-    pub fn solution(&self) -> Result<&Vec<u8>, &'static str> {
-        if self.solutions_left() == 1 {
-            Ok(&self.possible_values[0])
+    /// Removes the rule at `position` in the rule list and puts back the
+    /// candidates only it ruled out. If the domains widen beyond the
+    /// candidates being tracked, the widened domains are gone through
+    /// from scratch instead, which also applies any rules that haven't
+    /// been yet.
+    fn take_back(&mut self, position: usize) -> (String, Expression) {
+        let id = self.rule_list.ids[position];
+        let removed = self.rule_list.remove(position);
+        if position < self.applied_rules {
+            self.applied_rules -= 1;
+        }
+        self.domains = self.base_domains.clone();
+        self.min_length = self.base_min_length;
+        self.propagate();
+        if !self.space.covers(&self.domains, self.min_length) {
+            // Widened too far to go through, the rules are left for the
+            // next `eliminate_combinations` to refuse.
+            self.start_over(Space::domains(&self.domains, self.min_length));
+            if self.space.size().is_some_and(|size| size <= MAX_CANDIDATES) {
+                self.apply_rules();
+            }
         } else {
-            Err("Nope")
+            let space = &self.space;
+            let checks = self.rule_list.checks(space, 0..self.applied_rules);
+            let tags: Vec<u32> = checks.iter().map(|&(tag, _)| tag).collect();
+            self.candidates.restore_batches(tag(id), &tags, BATCH_SIZE, self.threads, || verdict(space, &checks));
+            self.collapsed.set(None);
         }
+        removed
     }
-}
 
-struct CombinationGenerator {
-    length: usize,
-    count: usize,
-}
+    /// Undoes the last rule added or taken back, returning its id. A
+    /// rule put back by undoing takes effect at the next
+    /// `eliminate_combinations`, like a newly added one.
+    ///
+    /// ```
+    /// use passcode_cracker::{Passcode, Alphabet};
+    ///
+    /// let mut passcode = Passcode::new(2, Alphabet::decimal()).unwrap();
+    /// passcode.add_rule("a = 1").unwrap();
+    /// passcode.add_rule("b = 1").unwrap();
+    /// passcode.eliminate_combinations().unwrap();
+    /// assert_eq!(passcode.solutions_left(), 1);
+    /// assert_eq!(passcode.undo(), Some(1));
+    /// assert_eq!(passcode.solutions_left(), 10);
+    /// assert_eq!(passcode.redo(), Some(1));
+    /// passcode.eliminate_combinations().unwrap();
+    /// assert_eq!(passcode.solutions_left(), 1);
+    /// ```
+    pub fn undo(&mut self) -> Option<usize> {
+        let edit = self.history.pop()?;
+        let id = edit.id;
+        let edit = self.replay(edit, false);
+        self.undone.push(edit);
+        Some(id)
+    }
 
-impl CombinationGenerator {
-    fn new(length: usize) -> CombinationGenerator {
-        CombinationGenerator { length: length, count: 0 }
+    /// Redoes the last change `undo` undid, returning the id of its
+    /// rule.
+    pub fn redo(&mut self) -> Option<usize> {
+        let edit = self.undone.pop()?;
+        let id = edit.id;
+        let edit = self.replay(edit, true);
+        self.history.push(edit);
+        Some(id)
     }
-}
 
-impl Iterator for CombinationGenerator {
-    type Item = Vec<u8>;
-    fn next(&mut self) -> Option<Vec<u8>> {
-        if self.count >= 10usize.pow(self.length as u32) {
+    /// Makes an edit again, or undoes it when not `forward`.
+    fn replay(&mut self, edit: Edit, forward: bool) -> Edit {
+        if edit.added == forward {
+            self.rule_list.insert(edit.id, edit.text.clone(), edit.rule.clone());
+        } else if let Some(position) = self.rule_list.position(edit.id) {
+            self.take_back(position);
+        }
+        edit
+    }
+
+    pub fn alphabet(&self) -> &Alphabet {
+        &self.alphabet
+    }
+
+    /// The shortest and longest length the passcode may have.
+    pub fn lengths(&self) -> RangeInclusive<usize> {
+        self.min_length..=self.domains.len()
+    }
+
+    /// The symbol values allowed at each position, up to the longest
+    /// length the passcode may have. These get narrowed down by
+    /// `propagate` as rules are added.
+    pub fn domains(&self) -> &[Vec<u8>] {
+        &self.domains
+    }
+
+    /// The number of combinations the passcode started out with,
+    /// before any rules were applied, or `None` if there are more than a
+    /// `u64` can count.
+    pub fn search_space(&self) -> Option<u64> {
+        self.search_space
+    }
+
+    pub fn lock(&self) -> &Lock {
+        &self.lock
+    }
+
+    /// Renders a combination using the passcode's alphabet, in the way
+    /// its kind of lock is written down.
+    pub fn format(&self, combination: &[u8]) -> String {
+        self.lock.format(&self.alphabet, combination)
+    }
+
+    /// Reads a combination written the way `format` writes it.
+    pub fn parse(&self, text: &str) -> Result<Vec<u8>, Error> {
+        Ok(self.lock.parse(&self.alphabet, text)?)
+    }
+
+    /// The rules a combination breaks, each with what every part of it
+    /// came to for the combination, to show why the combination isn't
+    /// possible.
+    ///
+    /// ```
+    /// use passcode_cracker::{Passcode, Alphabet};
+    ///
+    /// let mut passcode = Passcode::new(4, Alphabet::decimal()).unwrap();
+    /// passcode.add_rule("a + b = 5").unwrap();
+    /// passcode.add_rule("c < d").unwrap();
+    /// let failures = passcode.explain(&passcode.parse("1234").unwrap());
+    /// assert_eq!(failures.len(), 1);
+    /// assert_eq!(failures[0].rule, "a + b = 5");
+    /// assert_eq!(failures[0].annotated.parts[0].value, Ok("3".to_owned()));
+    /// println!("{}", failures[0].annotated);
+    /// ```
+    pub fn explain(&self, combination: &[u8]) -> Vec<Failure> {
+        let rule_list = &self.rule_list;
+        (0..rule_list.rules.len()).filter(|i| !rule_list.programs[*i].holds(combination)).map(|i| Failure {
+            id: rule_list.ids[i],
+            rule: rule_list.texts[i].clone(),
+            annotated: Annotated::new(&rule_list.rules[i], combination, &self.alphabet),
+        }).collect()
+    }
+
+    /// Finds rules that contradict each other when no combination
+    /// satisfies every rule, so that at least one of them must be wrong.
+    /// None of the rules found can be left out, as the rest of them
+    /// can all be satisfied without it. Returns their ids, or `None` if
+    /// some combination satisfies every rule.
+    ///
+    /// ```
+    /// use passcode_cracker::{Passcode, Alphabet};
+    ///
+    /// let mut passcode = Passcode::new(3, Alphabet::decimal()).unwrap();
+    /// assert_eq!(passcode.conflict(), None);
+    /// passcode.add_rules(&["a > 5", "c = 1", "b = 2", "a + b < 7"]).unwrap();
+    /// passcode.eliminate_combinations().unwrap();
+    /// assert_eq!(passcode.solutions_left(), 0);
+    /// assert_eq!(passcode.conflict(), Some(vec![0, 2, 3]));
+    /// ```
+    pub fn conflict(&self) -> Option<Vec<usize>> {
+        let mut core: Vec<usize> = (0..self.rule_list.rules.len()).collect();
+        if self.satisfiable(&core) {
             return None;
         }
-        let mut ret_vec: Vec<u8> = vec![0; self.length];
-        let mut count_copy = self.count;
-        for i in (0..self.length).rev() {
-            ret_vec[i] = (count_copy % 10) as u8;
-            count_copy /= 10;
-            if count_copy == 0 {
-                break;
+        // Drops the rules one at a time, oldest first, keeping only the
+        // ones the rest stop contradicting without.
+        let mut i = 0;
+        while i < core.len() {
+            let without: Vec<usize> = core.iter().enumerate().filter(|&(j, _)| j != i).map(|(_, p)| *p).collect();
+            if self.satisfiable(&without) {
+                i += 1;
+            } else {
+                core = without;
             }
         }
-        self.count += 1;
-        Some(ret_vec)
+        Some(core.iter().map(|p| self.rule_list.ids[*p]).collect())
+    }
+
+    /// Whether any combination the passcode started out with satisfies
+    /// the rules at `positions` in the rule list.
+    fn satisfiable(&self, positions: &[usize]) -> bool {
+        let rules: Vec<Expression> = positions.iter().map(|p| self.rule_list.rules[*p].clone()).collect();
+        match self.space {
+            Space::Domains(_) => {
+                let mut domains = self.base_domains.clone();
+                propagate::propagate(&mut domains, &rules);
+                domains.iter().all(|d| !d.is_empty()) && Search::new(&domains, self.base_min_length, &rules).next().is_some()
+            },
+            Space::Table(_) => {
+                let mut combination = Vec::new();
+                // Tables always know their size.
+                (0..self.space.size().unwrap_or(0)).any(|index| {
+                    self.space.decode(index, &mut combination);
+                    positions.iter().all(|p| self.rule_list.programs[*p].holds(&combination))
+                })
+            },
+        }
+    }
+
+    /// Every candidate that's still possible, decoded one at a time.
+    ///
+    /// ```
+    /// use passcode_cracker::{Passcode, Alphabet};
+    ///
+    /// let mut passcode = Passcode::new(2, Alphabet::decimal()).unwrap();
+    /// passcode.add_rule("a * b = 12").unwrap();
+    /// passcode.eliminate_combinations().unwrap();
+    /// let candidates: Vec<Vec<u8>> = passcode.candidates().collect();
+    /// assert_eq!(candidates, vec![vec![2, 6], vec![3, 4], vec![4, 3], vec![6, 2]]);
+    /// ```
+    pub fn candidates(&self) -> Candidates<'_> {
+        Candidates { space: &self.space, indices: self.candidates.iter() }
+    }
+
+    /// Finds the candidates that satisfy every rule without going
+    /// through the whole search space, one at a time and in the same
+    /// order as `candidates`. Positions are assigned one after another,
+    /// and a rule is checked as soon as every position it reads has
+    /// been, so passcodes far too long for `eliminate_combinations` can
+    /// be solved when the rules pin them down well enough. Locks that
+    /// don't have a value per position, like push-button locks and
+    /// patterns, are searched by checking each candidate instead.
+    ///
+    /// ```
+    /// use passcode_cracker::{Passcode, Alphabet};
+    ///
+    /// let mut passcode = Passcode::new(12, Alphabet::decimal()).unwrap();
+    /// for rule in &["a + b = 17", "b > a", "c = a", "d = b", "e + f = 0", "g = h", "h = i"] {
+    ///     passcode.add_rule(rule).unwrap();
+    /// }
+    /// for rule in &["i + j = 4", "j = k", "k * l = 9", "l = 3", "g > 0"] {
+    ///     passcode.add_rule(rule).unwrap();
+    /// }
+    /// let solutions: Vec<Vec<u8>> = passcode.search().collect();
+    /// assert_eq!(solutions, vec![vec![8, 9, 8, 9, 0, 0, 1, 1, 1, 3, 3, 3]]);
+    /// ```
+    pub fn search(&self) -> Box<dyn Iterator<Item = Vec<u8>> + '_> {
+        match self.space {
+            Space::Domains(_) => Box::new(Search::new(&self.domains, self.min_length, &self.rule_list.rules)),
+            Space::Table(_) => Box::new(self.candidates().filter(move |c| self.rule_list.run_rules(c))),
+        }
+    }
+
+    /// Splits the solutions into groups of positions that no rule links
+    /// together and solves each group on its own, so solutions can be
+    /// counted, listed and sampled without going through every
+    /// combination of the groups. This needs a passcode of a fixed
+    /// length with a value per position, whose rules only refer to
+    /// positions counted from the start, such as `a` or `d[27]`.
+    ///
+    /// ```
+    /// use passcode_cracker::{Passcode, Alphabet};
+    ///
+    /// let mut passcode = Passcode::new(20, Alphabet::decimal()).unwrap();
+    /// passcode.add_rule("a + b = 9").unwrap();
+    /// passcode.add_rule("c = d").unwrap();
+    /// let factored = passcode.factor().unwrap();
+    /// assert_eq!(factored.components().len(), 18);
+    /// assert_eq!(factored.count(), 10 * 10 * 10u128.pow(16));
+    /// let sample = factored.sample(1, 42).remove(0);
+    /// assert_eq!(sample[0] + sample[1], 9);
+    /// assert_eq!(sample[2], sample[3]);
+    /// ```
+    pub fn factor(&self) -> Option<Factored> {
+        if self.min_length != self.domains.len() {
+            return None;
+        }
+        match self.space {
+            Space::Domains(_) => Factored::new(&self.domains, &self.rule_list.rules),
+            Space::Table(_) => None,
+        }
+    }
+
+    /// What's certain about the code given the candidates still
+    /// possible: the values each position can hold, the positions that
+    /// are fixed or always equal to each other, and the properties every
+    /// candidate has. Returns `None` if no candidate is left.
+    ///
+    /// ```
+    /// use passcode_cracker::{Passcode, Alphabet, Property};
+    ///
+    /// let mut passcode = Passcode::new(4, Alphabet::decimal()).unwrap();
+    /// passcode.add_rules(&["a + b = 15", "b > a", "c = 4", "d = 2"]).unwrap();
+    /// passcode.eliminate_combinations().unwrap();
+    /// let facts = passcode.forced_facts().unwrap();
+    /// assert_eq!(facts.domains[0], vec![6, 7]);
+    /// assert_eq!(facts.fixed, vec![(2, 4), (3, 2)]);
+    /// assert_eq!(facts.properties, vec![Property::Distinct]);
+    /// ```
+    pub fn forced_facts(&self) -> Option<Facts> {
+        Facts::gather(self.candidates(), self.domains.len(), &self.lock, &self.alphabet)
+    }
+
+    /// How often each value appears at each position among the
+    /// candidates still possible, along with the `top` candidates that
+    /// have the most in common with the rest.
+    ///
+    /// ```
+    /// use passcode_cracker::{Passcode, Alphabet};
+    ///
+    /// let mut passcode = Passcode::new(2, Alphabet::decimal()).unwrap();
+    /// passcode.add_rule("a * b = 12").unwrap();
+    /// passcode.eliminate_combinations().unwrap();
+    /// let report = passcode.report(1);
+    /// assert_eq!(report.total, 4);
+    /// assert_eq!(report.frequencies[0], vec![(2, 1), (3, 1), (4, 1), (6, 1)]);
+    /// assert_eq!(report.most_common, vec![(vec![2, 6], 2)]);
+    /// ```
+    pub fn report(&self, top: usize) -> Report {
+        let length = self.domains.len();
+        if self.collapse {
+            let representatives = self.representatives();
+            Report::gather(|| representatives.iter().cloned(), length, top)
+        } else {
+            Report::gather(|| self.candidates(), length, top)
+        }
+    }
+
+    /// Suggests the `k` questions about the code whose answers are
+    /// expected to rule out the most candidates, best first. Each is a
+    /// rule that can be added as it is when the answer is yes, along
+    /// with the rule for no where there is one. The questions are
+    /// comparisons, parity, membership in a pair of values, sums of two
+    /// positions, and the lock's predicates.
+    ///
+    /// ```
+    /// use passcode_cracker::{Passcode, Alphabet};
+    ///
+    /// let mut passcode = Passcode::new(2, Alphabet::decimal()).unwrap();
+    /// passcode.add_rule("a + b = 9").unwrap();
+    /// passcode.eliminate_combinations().unwrap();
+    /// let queries = passcode.suggest_queries(3);
+    /// assert_eq!(queries[0].rule, "a > 4");
+    /// assert_eq!(queries[0].if_not, Some("a < 5".to_owned()));
+    /// assert_eq!(queries[0].gain, 1.0);
+    /// passcode.add_rule(&queries[0].rule).unwrap();
+    /// assert_eq!(passcode.eliminate_combinations(), Ok(5));
+    /// ```
+    pub fn suggest_queries(&self, k: usize) -> Vec<Query> {
+        let survivors = self.sample(QUERY_SAMPLE);
+        let context = ast::Context { total_vars: self.domains.len(), alphabet: &self.alphabet, lock: &self.lock };
+        let rules = suggest::templates(&self.lock, &self.alphabet, &self.domains, self.min_length);
+        suggest::rank(rules, &context, &survivors, k)
+    }
+
+    /// Adds the feedback a guess got, as in Mastermind, as two rules:
+    /// `exact(...)` for the positions the guess got right and
+    /// `misplaced(...)` for the symbols it got at the wrong position.
+    /// This only works for codes and dials. Returns the ids of the rules.
+    ///
+    /// ```
+    /// use passcode_cracker::{Passcode, Alphabet, Feedback};
+    ///
+    /// let mut passcode = Passcode::new(3, Alphabet::decimal()).unwrap();
+    /// passcode.add_feedback(&[1, 2, 3], Feedback { exact: 1, misplaced: 2 }).unwrap();
+    /// assert_eq!(passcode.rules(), vec![(0, "exact(1, 2, 3) = 1"), (1, "misplaced(1, 2, 3) = 2")]);
+    /// passcode.eliminate_combinations().unwrap();
+    /// assert_eq!(passcode.solutions_left(), 3);
+    /// ```
+    pub fn add_feedback(&mut self, guess: &[u8], feedback: Feedback) -> Result<Vec<usize>, Error> {
+        if let Some(value) = guess.iter().find(|v| self.alphabet.symbol_for(**v).is_none()) {
+            return Err(CombinationError::UnknownValue(*value).into());
+        }
+        let exact = Expression::counts(Predicate::Exact(guess.to_vec()), feedback.exact);
+        let misplaced = Expression::counts(Predicate::Misplaced(guess.to_vec()), feedback.misplaced);
+        {
+            let context = ast::Context { total_vars: self.domains.len(), alphabet: &self.alphabet, lock: &self.lock };
+            predicate::check_guess("exact", guess.len(), &context).map_err(|error| Error::Rule { rule: exact.render(&self.alphabet), error })?;
+        }
+        let rules = vec![(exact.render(&self.alphabet), exact), (misplaced.render(&self.alphabet), misplaced)];
+        Ok(self.push_rules(rules))
+    }
+
+    /// Picks the next guess out of the candidates still possible with
+    /// `strategy`, by how the feedback it could get splits them up.
+    /// When more than a couple of thousand are left, the guess is picked
+    /// out of an even sample of them, by how it splits the sample.
+    /// Returns `None` if nothing is possible.
+    ///
+    /// ```
+    /// use passcode_cracker::{Passcode, Alphabet, Strategy};
+    ///
+    /// let passcode = Passcode::new(4, Alphabet::from_chars("123456").unwrap()).unwrap();
+    /// let guess = passcode.next_guess(Strategy::Minimax).unwrap();
+    /// assert_eq!(passcode.format(&guess), "1122");
+    /// ```
+    pub fn next_guess(&self, strategy: Strategy) -> Option<Vec<u8>> {
+        let possible = self.sample(GUESS_SAMPLE);
+        strategy::best_guess(&possible, &possible, strategy)
+    }
+
+    /// How many more guesses picked with `strategy` it takes to find the
+    /// code, at worst and on average over the candidates still possible.
+    /// Every candidate is played out, so this takes a while when many
+    /// are left. Returns `None` if nothing is possible.
+    ///
+    /// ```
+    /// use passcode_cracker::{Passcode, Alphabet, Strategy};
+    ///
+    /// let passcode = Passcode::new(2, Alphabet::from_chars("1234").unwrap()).unwrap();
+    /// let outlook = passcode.guesses_left(Strategy::Minimax).unwrap();
+    /// assert!(outlook.worst <= 4 && outlook.average < 3.0);
+    /// ```
+    pub fn guesses_left(&self, strategy: Strategy) -> Option<Outlook> {
+        strategy::outlook(&self.representatives(), strategy, GUESS_SAMPLE)
+    }
+
+    /// Up to about `count` of the candidates still possible, spread out
+    /// evenly, near-identical ones collapsed into one when collapsing is
+    /// turned on. Only the candidates sampled are decoded.
+    fn sample(&self, count: usize) -> Vec<Vec<u8>> {
+        if self.collapse {
+            let representatives = self.representatives();
+            let step = representatives.len().div_ceil(count).max(1);
+            return representatives.into_iter().step_by(step).collect();
+        }
+        let step = (self.candidates.len() as usize).div_ceil(count).max(1);
+        self.candidates.iter().step_by(step).map(|index| {
+            let mut combination = Vec::with_capacity(self.space.max_length());
+            self.space.decode(index, &mut combination);
+            combination
+        }).collect()
+    }
+
+    /// Prints a table of how often each symbol appears at each position
+    /// among the candidates still possible, followed by the `top`
+    /// candidates that have the most in common with the rest. The rows
+    /// of push-button locks are buttons, and their columns the step
+    /// they're pressed at, `-` if they aren't.
+    pub fn print_report(&self, top: usize) {
+        let report = self.report(top);
+        if report.total == 0 {
+            return;
+        }
+        let table = match self.lock {
+            Lock::PushButton { .. } => {
+                let buttons: Vec<String> = (0..self.alphabet.len()).map(|i| self.alphabet.symbol(i).unwrap_or("?").to_owned()).collect();
+                let steps: Vec<u8> = (0..=buttons.len() as u8).collect();
+                report.table(&buttons, &steps, |step| if step == 0 { "-".to_owned() } else { step.to_string() })
+            },
+            _ => {
+                let positions: Vec<String> = (0..self.domains.len()).map(position_name).collect();
+                report.table(&positions, self.alphabet.values(), |value| self.alphabet.symbol_for(value).unwrap_or("?").to_owned())
+            },
+        };
+        print!("{}", table);
+        println!("Most common:");
+        for (candidate, _) in report.most_common.iter() {
+            println!("  {}", self.format(candidate));
+        }
+    }
+
+    /// Turns collapsing of near-identical candidates on or off. While on,
+    /// candidates the lock can't tell apart, like dial numbers within
+    /// tolerance of each other, are counted, printed and solved as one.
+    ///
+    /// ```
+    /// use passcode_cracker::{Passcode, Alphabet};
+    ///
+    /// let mut passcode = Passcode::dial(3, Alphabet::dial(40).unwrap(), 1).unwrap();
+    /// passcode.add_rule("a = 10").unwrap();
+    /// passcode.add_rule("b = 20").unwrap();
+    /// passcode.add_rule("c > 28").unwrap();
+    /// passcode.add_rule("c < 32").unwrap();
+    /// passcode.eliminate_combinations().unwrap();
+    /// assert_eq!(passcode.solutions_left(), 3);
+    /// passcode.collapse_within_tolerance(true);
+    /// assert_eq!(passcode.solutions_left(), 1);
+    /// assert_eq!(passcode.format(&passcode.solution().unwrap()), "10-20-30");
+    /// ```
+    pub fn collapse_within_tolerance(&mut self, collapse: bool) {
+        self.collapse = collapse;
+        self.collapsed.set(None);
+    }
+
+    /// The candidates still possible, with near-identical candidates
+    /// collapsed into one when collapsing is turned on.
+    pub fn representatives(&self) -> Vec<Vec<u8>> {
+        let mut candidates: Vec<Vec<u8>> = self.candidates().collect();
+        if !self.collapse {
+            return candidates;
+        }
+        let mut keep = vec![false; candidates.len()];
+        for i in self.lock.collapse(&candidates) {
+            keep[i] = true;
+        }
+        let mut i = 0;
+        candidates.retain(|_| { i += 1; keep[i - 1] });
+        candidates
+    }
+
+    /// The number of candidates still possible. This is kept track of
+    /// as candidates are eliminated, and counted again once they change
+    /// when collapsing is turned on. Before any are, it's every
+    /// combination the passcode started out with, capped at `usize::MAX`
+    /// when `search_space` can't count them.
+    pub fn solutions_left(&self) -> usize {
+        if self.collapse {
+            let count = self.collapsed.get().unwrap_or_else(|| self.representatives().len());
+            self.collapsed.set(Some(count));
+            count
+        } else {
+            self.candidates.len() as usize
+        }
+    }
+
+    pub fn solution_exists(&self) -> bool {
+        self.solutions_left() > 0
+    }
+
+    /// Whether the rules have narrowed things down to a single
+    /// combination, to none at all, or to several, of which the first
+    /// few are given as a sample.
+    ///
+    /// ```
+    /// use passcode_cracker::{Passcode, Alphabet, SolveState};
+    ///
+    /// let mut passcode = Passcode::new(2, Alphabet::decimal()).unwrap();
+    /// passcode.add_rule("a + b = 17").unwrap();
+    /// passcode.eliminate_combinations().unwrap();
+    /// assert_eq!(passcode.solve_state(), SolveState::Ambiguous { count: 2, sample: vec![vec![8, 9], vec![9, 8]] });
+    /// passcode.add_rule("a > b").unwrap();
+    /// passcode.eliminate_combinations().unwrap();
+    /// assert_eq!(passcode.solve_state(), SolveState::Unique(vec![9, 8]));
+    /// passcode.add_rule("b = 0").unwrap();
+    /// passcode.eliminate_combinations().unwrap();
+    /// assert_eq!(passcode.solve_state(), SolveState::Contradiction);
+    /// ```
+    pub fn solve_state(&self) -> SolveState {
+        let mut left = if self.collapse {
+            let representatives = self.representatives();
+            self.collapsed.set(Some(representatives.len()));
+            representatives
+        } else {
+            self.candidates().take(SAMPLE_SIZE).collect()
+        };
+        match self.solutions_left() {
+            0 => SolveState::Contradiction,
+            1 => SolveState::Unique(left.remove(0)),
+            count => {
+                left.truncate(SAMPLE_SIZE);
+                SolveState::Ambiguous { count, sample: left }
+            },
+        }
+    }
+
+    /// The combination that opens the lock, once there's only one left.
+    pub fn solution(&self) -> Result<Vec<u8>, Error> {
+        match self.solve_state() {
+            SolveState::Unique(combination) => Ok(combination),
+            SolveState::Contradiction => Err(Error::Contradiction),
+            SolveState::Ambiguous { count, .. } => Err(Error::Ambiguous(count)),
+        }
     }
 }
 
@@ -166,7 +1221,7 @@ impl Iterator for CombinationGenerator {
 fn bench_first_for_loop(b: &mut Bencher) {
     b.iter(|| {
         let total_combinations: usize = 10000;
-        let mut combinations: Vec<Vec<u8>> = vec![Vec::with_capacity(4 as usize); total_combinations];
+        let mut combinations: Vec<Vec<u8>> = (0..total_combinations).map(|_| Vec::with_capacity(4)).collect();
         let mut chunksize = 1;
         while chunksize < total_combinations {
             let mut current_val = 0;
@@ -175,9 +1230,9 @@ fn bench_first_for_loop(b: &mut Bencher) {
                     combination.push(current_val);
                 }
                 current_val += 1;
-                current_val = current_val % 10;
+                current_val %= 10;
             }
-            chunksize = chunksize * 10;
+            chunksize *= 10;
         }
     });
 }
@@ -185,7 +1240,247 @@ fn bench_first_for_loop(b: &mut Bencher) {
 #[bench]
 fn bench_new_generator(b: &mut Bencher) {
     b.iter(|| {
-        let _: Vec<Vec<u8>> = test::black_box(CombinationGenerator::new(4).collect());
+        let _: Vec<Vec<u8>> = test::black_box(CombinationGenerator::new(vec![Alphabet::decimal().values().to_vec(); 4]).collect());
+    });
+}
+
+
+/// Eliminates the 90% of a decimal space that fails `a + b = 9`.
+#[cfg(test)]
+fn bench_eliminate(b: &mut Bencher, length: usize) {
+    b.iter(|| {
+        let mut passcode = Passcode::new(length, Alphabet::decimal()).unwrap();
+        passcode.add_rule("a + b = 9").unwrap();
+        test::black_box(passcode.eliminate_combinations())
     });
 }
 
+#[bench]
+fn bench_eliminate_4(b: &mut Bencher) {
+    bench_eliminate(b, 4);
+}
+
+#[bench]
+fn bench_eliminate_5(b: &mut Bencher) {
+    bench_eliminate(b, 5);
+}
+
+#[bench]
+fn bench_eliminate_6(b: &mut Bencher) {
+    bench_eliminate(b, 6);
+}
+
+// Too slow to run unoptimized as part of `cargo test`.
+#[bench]
+#[cfg(not(debug_assertions))]
+fn bench_eliminate_7(b: &mut Bencher) {
+    bench_eliminate(b, 7);
+}
+
+#[bench]
+#[cfg(not(debug_assertions))]
+fn bench_eliminate_8(b: &mut Bencher) {
+    bench_eliminate(b, 8);
+}
+
+/// Eliminates the same candidates as `bench_eliminate` the way it used
+/// to be done, for comparison: every combination is collected into a
+/// `Vec` like `bench_new_generator` does, and the ones the rule fails are
+/// removed one at a time, each removal shifting the rest down.
+#[cfg(test)]
+fn bench_eliminate_by_removal(b: &mut Bencher, length: usize) {
+    let alphabet = Alphabet::decimal();
+    let rule = ast::parse_code("a + b = 9", length);
+    b.iter(|| {
+        let mut possible: Vec<Vec<u8>> = CombinationGenerator::new(vec![alphabet.values().to_vec(); length]).collect();
+        let bad: Vec<usize> = possible.iter().enumerate().filter(|&(_, combination)| !rule.holds(combination)).map(|(i, _)| i).collect();
+        for i in bad.iter().rev() {
+            possible.remove(*i);
+        }
+        test::black_box(bad.len())
+    });
+}
+
+#[bench]
+fn bench_eliminate_by_removal_4(b: &mut Bencher) {
+    bench_eliminate_by_removal(b, 4);
+}
+
+// Quadratic, so only worth waiting for when optimized.
+#[bench]
+#[cfg(not(debug_assertions))]
+fn bench_eliminate_by_removal_5(b: &mut Bencher) {
+    bench_eliminate_by_removal(b, 5);
+}
+
+/// Eliminates using a rule that reads the whole combination, so it has
+/// to be run on every candidate rather than looked up.
+#[cfg(all(test, not(debug_assertions)))]
+fn bench_eliminate_program(b: &mut Bencher, length: usize, threads: Option<usize>) {
+    b.iter(|| {
+        let mut passcode = Passcode::new(length, Alphabet::decimal()).unwrap();
+        if let Some(threads) = threads {
+            passcode.use_threads(threads);
+        }
+        passcode.add_rule("a + b + c = d[-1]").unwrap();
+        test::black_box(passcode.eliminate_combinations())
+    });
+}
+
+#[bench]
+#[cfg(not(debug_assertions))]
+fn bench_eliminate_program_7(b: &mut Bencher) {
+    bench_eliminate_program(b, 7, None);
+}
+
+#[bench]
+#[cfg(not(debug_assertions))]
+fn bench_eliminate_program_8(b: &mut Bencher) {
+    bench_eliminate_program(b, 8, None);
+}
+
+#[bench]
+#[cfg(not(debug_assertions))]
+fn bench_eliminate_program_7_one_thread(b: &mut Bencher) {
+    bench_eliminate_program(b, 7, Some(1));
+}
+
+/// Runs the rule of `bench_eliminate_program` the way it was before
+/// candidates were checked in batches, for comparison: each candidate is
+/// decoded on its own and the program run on it, on a single thread.
+#[cfg(all(test, not(debug_assertions)))]
+fn bench_eliminate_per_candidate(b: &mut Bencher, length: usize) {
+    let alphabet = Alphabet::decimal();
+    let program = Program::compile(&ast::parse_code("a + b + c = d[-1]", length));
+    b.iter(|| {
+        let space = Space::domains(&vec![alphabet.values().to_vec(); length], length);
+        let size = space.size().unwrap();
+        let mut alive = vec![0u64; size.div_ceil(64) as usize];
+        let mut combination = Vec::with_capacity(length);
+        for index in 0..size {
+            space.decode(index, &mut combination);
+            if program.holds(&combination) {
+                alive[(index / 64) as usize] |= 1 << (index % 64);
+            }
+        }
+        test::black_box(alive)
+    });
+}
+
+#[bench]
+#[cfg(not(debug_assertions))]
+fn bench_eliminate_per_candidate_7(b: &mut Bencher) {
+    bench_eliminate_per_candidate(b, 7);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions_past_z() {
+        // Only a, d[27] and d[28] vary, the 25 positions in between are 0.
+        let mut passcode = Passcode::with_mask(&format!("?d{}?d?d", "0".repeat(25))).unwrap();
+        passcode.add_rules(&["d[27] = 1", "a = 1"]).unwrap();
+        assert_eq!(passcode.rule_list.rules[0].render(passcode.alphabet()), "d[27] = 1");
+        let solutions: Vec<Vec<u8>> = passcode.search().collect();
+        assert_eq!(solutions.len(), 10);
+        assert!(solutions.iter().all(|s| s[0] == 1 && s[26] == 1));
+        assert_eq!(passcode.factor().unwrap().count(), 10);
+
+        let mut combination = solutions[0].clone();
+        assert!(passcode.rule_list.rules.iter().all(|rule| rule.holds(&combination)));
+        assert!(passcode.explain(&combination).is_empty());
+        combination[26] = 2;
+        assert!(!passcode.rule_list.rules[0].holds(&combination));
+        assert_eq!(passcode.explain(&combination).len(), 1);
+
+        passcode.eliminate_combinations().unwrap();
+        assert_eq!(passcode.candidates().collect::<Vec<Vec<u8>>>(), solutions);
+    }
+
+    #[test]
+    fn lengths_past_the_most() {
+        let mut passcode = Passcode::new(MAX_LENGTH, Alphabet::from_chars("0").unwrap()).unwrap();
+        passcode.add_rule(&format!("d[{}] = len - {}", MAX_LENGTH, MAX_LENGTH)).unwrap();
+        passcode.eliminate_combinations().unwrap();
+        assert_eq!(passcode.solutions_left(), 1);
+        assert_eq!(Passcode::with_lengths(4..=MAX_LENGTH + 1, Alphabet::decimal()).err(), Some(Error::TooManyPositions(MAX_LENGTH + 1)));
+        assert_eq!(Passcode::dial(1000, Alphabet::dial(40).unwrap(), 1).err(), Some(Error::TooManyPositions(1000)));
+        let mask = Mask::parse(&"?d".repeat(MAX_LENGTH + 1)).unwrap();
+        assert_eq!(Passcode::from_mask(mask).err(), Some(Error::TooManyPositions(MAX_LENGTH + 1)));
+    }
+
+    #[test]
+    fn too_many_buttons() {
+        let labels: Vec<String> = (1..=40).map(|i| i.to_string()).collect();
+        let labels: Vec<&str> = labels.iter().map(|label| label.as_str()).collect();
+        let buttons = |count: usize| Alphabet::new(&labels[..count]).unwrap();
+        assert_eq!(Passcode::button_set(buttons(40)).err(), Some(Error::TooManyButtons { buttons: 40, most: 20 }));
+        assert_eq!(Passcode::button_set(buttons(21)).err(), Some(Error::TooManyButtons { buttons: 21, most: 20 }));
+        assert_eq!(Passcode::button_sequence(buttons(9)).err(), Some(Error::TooManyButtons { buttons: 9, most: 8 }));
+        assert_eq!(Passcode::button_set(buttons(12)).unwrap().solutions_left(), 1 << 12);
+    }
+
+    #[test]
+    fn too_many_candidates() {
+        let mut passcode = Passcode::new(12, Alphabet::decimal()).unwrap();
+        assert_eq!(passcode.eliminate_combinations(), Err(Error::TooManyCandidates(Some(10u64.pow(12)))));
+        assert_eq!(Passcode::new(20, Alphabet::decimal()).unwrap().search_space(), None);
+        // Once the rules narrow the domains down, there are few enough.
+        passcode.add_rules(&["a = 1", "b = 2", "c = 3", "d = 4", "e = 5", "f = 6"]).unwrap();
+        assert_eq!(passcode.eliminate_combinations(), Ok(10u64.pow(12) as usize - 10usize.pow(6)));
+        assert_eq!(passcode.solutions_left(), 10usize.pow(6));
+    }
+
+    #[test]
+    fn collapsed_count_follows_candidates() {
+        let mut passcode = Passcode::dial(2, Alphabet::dial(40).unwrap(), 1).unwrap();
+        passcode.collapse_within_tolerance(true);
+        let near = passcode.add_rule("a > 8").unwrap();
+        passcode.add_rules(&["a < 12", "b = 20"]).unwrap();
+        passcode.eliminate_combinations().unwrap();
+        assert_eq!(passcode.solutions_left(), 1);
+        passcode.remove_rule(near).unwrap();
+        let collapsed = passcode.representatives().len();
+        assert!(collapsed > 1);
+        assert_eq!(passcode.solutions_left(), collapsed);
+        passcode.collapse_within_tolerance(false);
+        assert_eq!(passcode.solutions_left(), 12);
+        passcode.add_rule("a > 10").unwrap();
+        passcode.eliminate_combinations().unwrap();
+        passcode.collapse_within_tolerance(true);
+        assert_eq!(passcode.solutions_left(), 1);
+    }
+
+    #[test]
+    fn feedback_on_any_symbols() {
+        let mut passcode = Passcode::new(3, Alphabet::printable()).unwrap();
+        let guess = passcode.parse(",)'").unwrap();
+        passcode.add_feedback(&guess, Feedback { exact: 3, misplaced: 0 }).unwrap();
+        let texts: Vec<String> = passcode.rules().iter().map(|&(_, text)| text.to_owned()).collect();
+        assert_eq!(texts, vec!["exact(',', ')', \"'\") = 3", "misplaced(',', ')', \"'\") = 0"]);
+        // The rules read back the way they were given.
+        let mut reread = Passcode::new(3, Alphabet::printable()).unwrap();
+        reread.add_rules(&[&texts[0], &texts[1]]).unwrap();
+        assert_eq!(reread.rule_list.rules, passcode.rule_list.rules);
+        passcode.eliminate_combinations().unwrap();
+        assert_eq!(passcode.solution(), Ok(guess));
+
+        assert_eq!(passcode.add_feedback(&[1, 200, 3], Feedback { exact: 0, misplaced: 0 }), Err(Error::Combination(CombinationError::UnknownValue(200))));
+        assert!(passcode.add_feedback(&[1, 2, 3, 4], Feedback { exact: 0, misplaced: 0 }).is_err());
+    }
+
+    #[test]
+    fn guesses_in_bounded_time() {
+        use std::time::{Duration, Instant};
+
+        // Scoring every guess tried against all million candidates would
+        // take minutes.
+        let passcode = Passcode::new(6, Alphabet::decimal()).unwrap();
+        let start = Instant::now();
+        let guess = passcode.next_guess(Strategy::Minimax).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(10), "took {:?}", start.elapsed());
+        assert_eq!(guess.len(), 6);
+    }
+}
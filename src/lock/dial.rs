@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+
+/// How far apart two numbers are on a dial, going whichever way round
+/// is shorter.
+pub fn distance(a: u8, b: u8, size: usize) -> usize {
+    let difference = (a as isize - b as isize).unsigned_abs();
+    difference.min(size - difference)
+}
+
+/// Whether a dial that accepts `tolerance` numbers either side of the
+/// real combination opens for both `a` and `b` being the same code.
+pub fn within_tolerance(a: &[u8], b: &[u8], size: usize, tolerance: u8) -> bool {
+    a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| distance(*x, *y, size) <= tolerance as usize)
+}
+
+/// Picks a set of representatives such that every combination is within
+/// tolerance of one of them, so trying only the representatives is
+/// enough to open the lock. Combinations that cover the most others are
+/// picked first. Returns the indices of the representatives.
+pub fn collapse(combinations: &[Vec<u8>], size: usize, tolerance: u8) -> Vec<usize> {
+    let index: HashMap<&[u8], usize> = combinations.iter().enumerate().map(|(i, c)| (c.as_slice(), i)).collect();
+    let neighbours: Vec<Vec<usize>> = combinations.iter().map(|combination| {
+        let mut found: Vec<usize> = neighbourhood(combination, size, tolerance).iter()
+            .filter_map(|n| index.get(n.as_slice()).cloned())
+            .collect();
+        found.sort_unstable();
+        found.dedup();
+        found
+    }).collect();
+
+    let mut order: Vec<usize> = (0..combinations.len()).collect();
+    order.sort_by(|a, b| neighbours[*b].len().cmp(&neighbours[*a].len()));
+
+    let mut covered = vec![false; combinations.len()];
+    let mut representatives: Vec<usize> = Vec::new();
+    for i in order {
+        if covered[i] {
+            continue;
+        }
+        representatives.push(i);
+        for n in neighbours[i].iter() {
+            covered[*n] = true;
+        }
+    }
+    representatives
+}
+
+/// Every combination within tolerance of `combination`, itself included.
+fn neighbourhood(combination: &[u8], size: usize, tolerance: u8) -> Vec<Vec<u8>> {
+    let mut result: Vec<Vec<u8>> = vec![Vec::with_capacity(combination.len())];
+    for value in combination.iter() {
+        let mut next: Vec<Vec<u8>> = Vec::with_capacity(result.len() * (2 * tolerance as usize + 1));
+        for partial in result.iter() {
+            for offset in -(tolerance as isize)..=(tolerance as isize) {
+                let mut extended = partial.clone();
+                extended.push((*value as isize + offset).rem_euclid(size as isize) as u8);
+                next.push(extended);
+            }
+        }
+        result = next;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance_wraps() {
+        assert_eq!(distance(1, 39, 40), 2);
+        assert_eq!(distance(39, 1, 40), 2);
+        assert_eq!(distance(10, 30, 40), 20);
+        assert_eq!(distance(5, 5, 40), 0);
+    }
+
+    #[test]
+    fn collapse_neighbours() {
+        let combinations = vec![vec![9, 0], vec![10, 0], vec![11, 39], vec![20, 0]];
+        let representatives = collapse(&combinations, 40, 1);
+        assert_eq!(representatives, vec![1, 3]);
+        assert_eq!(collapse(&combinations, 40, 0).len(), 4);
+    }
+}
@@ -0,0 +1,84 @@
+mod push_button;
+mod dial;
+pub mod pattern;
+
+use alphabet::Alphabet;
+use error::CombinationError;
+
+/// The kind of lock a passcode is for. It decides what a candidate
+/// combination looks like, how it's printed, and which predicates
+/// rules can use on top of plain position variables.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Lock {
+    /// An ordered sequence of symbols, such as a PIN.
+    Code,
+    /// A mechanical push-button lock where every button can be used at
+    /// most once. A candidate holds, for each button in alphabet order,
+    /// the step it's pressed at, or 0 if it isn't pressed. Buttons that
+    /// share a step are pressed together. When the lock isn't `ordered`
+    /// only the set of pressed buttons matters, so every pressed button
+    /// is at step 1.
+    PushButton { ordered: bool },
+    /// A rotary dial padlock with `size` numbers on its dial. Arithmetic
+    /// in rules wraps around the dial, and the lock opens for any number
+    /// within `tolerance` of the real one on either side.
+    Dial { size: usize, tolerance: u8 },
+    /// An Android style unlock pattern of 4 to 9 dots on a 3x3 grid,
+    /// numbered 1 to 9 like a keypad. Positions hold the dots in the
+    /// order they're visited.
+    Pattern,
+}
+
+impl Lock {
+    /// Renders a combination for this kind of lock.
+    pub fn format(&self, alphabet: &Alphabet, combination: &[u8]) -> String {
+        match *self {
+            Lock::Code | Lock::Dial { .. } | Lock::Pattern => alphabet.format(combination),
+            Lock::PushButton { ordered } => push_button::format(alphabet, combination, ordered),
+        }
+    }
+
+    /// Reads a combination written the way `format` writes it.
+    pub fn parse(&self, alphabet: &Alphabet, text: &str) -> Result<Vec<u8>, CombinationError> {
+        match *self {
+            Lock::Code | Lock::Dial { .. } | Lock::Pattern => alphabet.parse(text.trim()),
+            Lock::PushButton { ordered } => push_button::parse(alphabet, text, ordered),
+        }
+    }
+
+    /// Picks out representatives of the combinations such that every
+    /// combination opens the lock whenever its representative does, and
+    /// returns their indices. Only dials have a tolerance, for other
+    /// locks every combination is its own representative.
+    pub fn collapse(&self, combinations: &[Vec<u8>]) -> Vec<usize> {
+        match *self {
+            Lock::Dial { size, tolerance } if tolerance > 0 => dial::collapse(combinations, size, tolerance),
+            _ => (0..combinations.len()).collect(),
+        }
+    }
+
+    /// Whether the lock would open for `b` when the real combination is
+    /// `a`, counting a dial's tolerance.
+    pub fn equivalent(&self, a: &[u8], b: &[u8]) -> bool {
+        match *self {
+            Lock::Dial { size, tolerance } => dial::within_tolerance(a, b, size, tolerance),
+            _ => a == b,
+        }
+    }
+
+    /// Every valid unlock pattern.
+    pub fn pattern_combinations() -> Vec<Vec<u8>> {
+        pattern::combinations()
+    }
+
+    /// The most buttons a push-button lock can have for every one of
+    /// its combinations to be listed.
+    pub fn most_buttons(ordered: bool) -> usize {
+        push_button::most_buttons(ordered)
+    }
+
+    /// Every candidate combination of a lock with `buttons` buttons.
+    pub fn push_button_combinations(buttons: usize, ordered: bool) -> Vec<Vec<u8>> {
+        push_button::combinations(buttons, ordered)
+    }
+}
@@ -0,0 +1,157 @@
+/// The dots of the 3x3 grid are numbered like a phone keypad:
+///
+/// ```text
+/// 1 2 3
+/// 4 5 6
+/// 7 8 9
+/// ```
+fn coordinates(dot: u8) -> (i32, i32) {
+    let index = dot as i32 - 1;
+    (index % 3, index / 3)
+}
+
+/// The dot a line from `from` to `to` passes straight over, if any.
+fn jumped_dot(from: u8, to: u8) -> Option<u8> {
+    let ((x1, y1), (x2, y2)) = (coordinates(from), coordinates(to));
+    if (x1 + x2) % 2 == 0 && (y1 + y2) % 2 == 0 {
+        Some((((y1 + y2) / 2) * 3 + (x1 + x2) / 2 + 1) as u8)
+    } else {
+        None
+    }
+}
+
+/// Builds every valid unlock pattern of 4 to 9 dots: no dot is used
+/// twice, and a line can only pass over a dot that's already been
+/// visited. There are 389,112 of them.
+pub fn combinations() -> Vec<Vec<u8>> {
+    let mut combinations: Vec<Vec<u8>> = Vec::with_capacity(389_112);
+    let mut current: Vec<u8> = Vec::with_capacity(9);
+    for length in 4..=9 {
+        for start in 1..=9 {
+            current.push(start);
+            extend(&mut current, length, &mut combinations);
+            current.pop();
+        }
+    }
+    combinations
+}
+
+fn extend(current: &mut Vec<u8>, length: usize, combinations: &mut Vec<Vec<u8>>) {
+    if current.len() == length {
+        combinations.push(current.clone());
+        return;
+    }
+    let last = *current.last().unwrap();
+    for next in 1..=9 {
+        if current.contains(&next) {
+            continue;
+        }
+        if let Some(jumped) = jumped_dot(last, next) {
+            if jumped != next && !current.contains(&jumped) {
+                continue;
+            }
+        }
+        current.push(next);
+        extend(current, length, combinations);
+        current.pop();
+    }
+}
+
+pub fn starts_at_corner(pattern: &[u8]) -> bool {
+    match pattern.first() {
+        Some(dot) => [1, 3, 7, 9].contains(dot),
+        None => false,
+    }
+}
+
+/// Whether two of the pattern's lines cross each other. A line that goes
+/// back over a visited dot counts as crossing the lines that dot joins,
+/// even where it only touches them.
+pub fn crosses(pattern: &[u8]) -> bool {
+    let lines: Vec<((i32, i32), (i32, i32))> = pattern.windows(2).map(|w| (coordinates(w[0]), coordinates(w[1]))).collect();
+    for i in 0..lines.len() {
+        for j in (i + 2)..lines.len() {
+            if lines_cross(lines[i], lines[j]) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+fn orientation(a: (i32, i32), b: (i32, i32), c: (i32, i32)) -> i32 {
+    ((b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)).signum()
+}
+
+fn lines_cross(first: ((i32, i32), (i32, i32)), second: ((i32, i32), (i32, i32))) -> bool {
+    let (a, b) = first;
+    let (c, d) = second;
+    let (o1, o2) = (orientation(a, b, c), orientation(a, b, d));
+    let (o3, o4) = (orientation(c, d, a), orientation(c, d, b));
+    if o1 * o2 < 0 && o3 * o4 < 0 {
+        return true;
+    }
+    // The lines of a pattern never share an end unless one leads into
+    // the other, so one ending on the other means it was passed over.
+    (o1 == 0 && within(a, b, c)) || (o2 == 0 && within(a, b, d)) || (o3 == 0 && within(c, d, a)) || (o4 == 0 && within(c, d, b))
+}
+
+/// Whether `point`, which lies on the same line as `a` and `b`, lies
+/// between them.
+fn within(a: (i32, i32), b: (i32, i32), point: (i32, i32)) -> bool {
+    a.0.min(b.0) <= point.0 && point.0 <= a.0.max(b.0) && a.1.min(b.1) <= point.1 && point.1 <= a.1.max(b.1)
+}
+
+/// How many times the pattern changes direction between one line and
+/// the next.
+pub fn turns(pattern: &[u8]) -> usize {
+    let directions: Vec<(i32, i32)> = pattern.windows(2).map(|w| {
+        let ((x1, y1), (x2, y2)) = (coordinates(w[0]), coordinates(w[1]));
+        let (dx, dy) = (x2 - x1, y2 - y1);
+        let divisor = gcd(dx.abs(), dy.abs());
+        (dx / divisor, dy / divisor)
+    }).collect();
+    directions.windows(2).filter(|w| w[0] != w[1]).count()
+}
+
+fn gcd(a: i32, b: i32) -> i32 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pattern_count() {
+        let patterns = combinations();
+        assert_eq!(patterns.len(), 389_112);
+        assert_eq!(patterns.iter().filter(|p| p.len() == 4).count(), 1_624);
+        assert_eq!(patterns.iter().filter(|p| p.len() == 9).count(), 140_704);
+    }
+
+    #[test]
+    fn jumping_needs_visited_dot() {
+        assert_eq!(jumped_dot(1, 3), Some(2));
+        assert_eq!(jumped_dot(1, 9), Some(5));
+        assert_eq!(jumped_dot(1, 6), None);
+        let patterns = combinations();
+        assert!(!patterns.contains(&vec![1, 3, 2, 5]));
+        assert!(patterns.contains(&vec![2, 1, 3, 5]));
+    }
+
+    #[test]
+    fn pattern_predicates() {
+        assert!(starts_at_corner(&[7, 4, 1, 2]));
+        assert!(!starts_at_corner(&[5, 4, 1, 2]));
+        assert!(crosses(&[1, 9, 3, 7]));
+        assert!(!crosses(&[1, 2, 3, 6, 9]));
+        assert!(!crosses(&[1, 5, 9, 8]));
+        // Back over the centre dot, across the lines that meet there.
+        assert!(crosses(&[4, 5, 2, 1, 9]));
+        assert!(crosses(&[5, 2, 8, 4, 6]));
+        assert_eq!(turns(&[1, 2, 3, 6, 9]), 1);
+        assert_eq!(turns(&[1, 5, 9, 8, 7]), 1);
+        assert_eq!(turns(&[1, 2, 5, 4, 7]), 3);
+    }
+}
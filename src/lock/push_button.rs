@@ -0,0 +1,136 @@
+use alphabet::Alphabet;
+use error::CombinationError;
+
+/// The most buttons a lock can have, which keeps it to about a million
+/// combinations. Ordered locks have far more combinations per button.
+pub fn most_buttons(ordered: bool) -> usize {
+    if ordered { 8 } else { 20 }
+}
+
+/// Builds every combination of a push-button lock. Without order that's
+/// every subset of the buttons; with order it's every ordered partition
+/// of every subset, so pressing 1 and 4 together and then 3 is a
+/// different combination from pressing 3 first. There must be at most
+/// `most_buttons(ordered)` buttons.
+pub fn combinations(buttons: usize, ordered: bool) -> Vec<Vec<u8>> {
+    let mut combinations: Vec<Vec<u8>> = Vec::new();
+    let mut current: Vec<u8> = vec![0; buttons];
+    for subset in 0..(1u32 << buttons) {
+        if ordered {
+            assign_steps(subset, 1, &mut current, &mut combinations);
+        } else {
+            for (button, step) in current.iter_mut().enumerate() {
+                *step = ((subset >> button) & 1) as u8;
+            }
+            combinations.push(current.clone());
+        }
+    }
+    combinations
+}
+
+/// Hands every non-empty subset of the `remaining` buttons the given
+/// step, then recurses on whatever is left for the following steps.
+fn assign_steps(remaining: u32, step: u8, current: &mut Vec<u8>, combinations: &mut Vec<Vec<u8>>) {
+    if remaining == 0 {
+        combinations.push(current.clone());
+        return;
+    }
+    let mut group = remaining;
+    while group != 0 {
+        set_step(current, group, step);
+        assign_steps(remaining & !group, step + 1, current, combinations);
+        set_step(current, group, 0);
+        group = (group - 1) & remaining;
+    }
+}
+
+fn set_step(current: &mut [u8], group: u32, step: u8) {
+    for (button, value) in current.iter_mut().enumerate() {
+        if (group >> button) & 1 == 1 {
+            *value = step;
+        }
+    }
+}
+
+/// Prints the buttons of each step joined by `+`, with the steps
+/// separated by commas, e.g. `1+4, 3`. Unordered combinations are
+/// printed as a set.
+pub fn format(alphabet: &Alphabet, combination: &[u8], ordered: bool) -> String {
+    let last_step = combination.iter().cloned().max().unwrap_or(0);
+    if last_step == 0 {
+        return "(nothing pressed)".to_owned();
+    }
+    let label = |button: usize| alphabet.symbol(button).unwrap_or("?").to_owned();
+    if !ordered {
+        let pressed: Vec<String> = (0..combination.len()).filter(|b| combination[*b] != 0).map(label).collect();
+        return format!("{{{}}}", pressed.join(", "));
+    }
+    let steps: Vec<String> = (1..=last_step).map(|step| {
+        let group: Vec<String> = (0..combination.len()).filter(|b| combination[*b] == step).map(label).collect();
+        group.join("+")
+    }).collect();
+    steps.join(", ")
+}
+
+/// Reads a combination written the way `format` writes it.
+pub fn parse(alphabet: &Alphabet, text: &str, ordered: bool) -> Result<Vec<u8>, CombinationError> {
+    let mut combination = vec![0; alphabet.len()];
+    let text = text.trim();
+    if text == "(nothing pressed)" {
+        return Ok(combination);
+    }
+    let text = if ordered { text } else { text.trim_start_matches('{').trim_end_matches('}') };
+    for (i, group) in text.split(',').enumerate() {
+        let step = if ordered { i + 1 } else { 1 };
+        for label in group.split('+').map(|label| label.trim()) {
+            let button = alphabet.index_of(label).ok_or_else(|| CombinationError::UnknownButton(label.to_owned()))?;
+            if combination[button] != 0 {
+                return Err(CombinationError::PressedTwice(label.to_owned()));
+            }
+            combination[button] = step as u8;
+        }
+    }
+    Ok(combination)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simplex_combination_count() {
+        assert_eq!(combinations(5, true).len(), 1082);
+        assert_eq!(combinations(5, false).len(), 32);
+        assert_eq!(combinations(3, true).len(), 1 + 3 + 3 * 3 + 13);
+    }
+
+    #[test]
+    fn steps_are_contiguous() {
+        for combination in combinations(4, true) {
+            let last_step = combination.iter().cloned().max().unwrap();
+            for step in 1..=last_step {
+                assert!(combination.contains(&step));
+            }
+        }
+    }
+
+    #[test]
+    fn format_steps() {
+        let alphabet = Alphabet::from_chars("12345").unwrap();
+        assert_eq!(format(&alphabet, &[1, 0, 2, 1, 0], true), "1+4, 3");
+        assert_eq!(format(&alphabet, &[1, 0, 1, 1, 0], false), "{1, 3, 4}");
+        assert_eq!(format(&alphabet, &[0, 0, 0, 0, 0], true), "(nothing pressed)");
+    }
+
+    #[test]
+    fn parse_what_format_writes() {
+        let alphabet = Alphabet::from_chars("12345").unwrap();
+        for &ordered in [true, false].iter() {
+            for combination in combinations(5, ordered) {
+                assert_eq!(parse(&alphabet, &format(&alphabet, &combination, ordered), ordered), Ok(combination));
+            }
+        }
+        assert!(parse(&alphabet, "1+4, 4", true).is_err());
+        assert!(parse(&alphabet, "1+6", true).is_err());
+    }
+}
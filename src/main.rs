@@ -2,52 +2,502 @@ extern crate passcode_cracker;
 
 use std::io;
 use std::io::Write;
-use passcode_cracker::Passcode;
+use passcode_cracker::{Passcode, Alphabet, Mask, Lock, Facts, Property, Error, Feedback, Strategy, position_name};
+use passcode_cracker::strategy;
+
+/// Search spaces bigger than this are searched one hint at a time
+/// instead of being enumerated up front. This is well under
+/// `MAX_CANDIDATES`, so eliminating combinations never fails.
+const ENUMERATION_LIMIT: u64 = 10_000_000;
+
+/// How many solutions to look for when searching before giving up on
+/// counting them all.
+const COUNT_LIMIT: usize = 1000;
+
+/// How many of the most common candidates a report lists.
+const REPORT_TOP: usize = 5;
+
+/// How many questions 'ask' suggests.
+const SUGGESTIONS: usize = 5;
+
+/// The most combinations a lock can have to offer guessing at it, as
+/// each guess means checking the feedback against every one of them.
+const CODEBREAK_LIMIT: u64 = 10_000;
+
+/// How many candidates can be left for the guesses left to be worked
+/// out before each guess.
+const OUTLOOK_LIMIT: usize = 500;
 
 fn main() {
     'main: loop {
-        let num_of_digits;
+        let mut num_of_digits = 0..=0;
+        let mut mask = None;
+        let mut buttons = false;
+        let mut dial = false;
+        let mut pattern = false;
         let mut input = String::new();
         'digit: loop {
-            println!("Number of digits: ");
+            println!("Number of digits, a range such as 4-6, a mask such as ?d?d[0-3]?l, 'buttons', 'dial' or 'pattern': ");
             io::stdin().read_line(&mut input).ok();
             input = input.trim().to_owned();
             if input == "q" {
                 break 'main;
             }
+            if input == "buttons" {
+                buttons = true;
+                break 'digit;
+            }
+            if input == "dial" {
+                dial = true;
+                break 'digit;
+            }
+            if input == "pattern" {
+                pattern = true;
+                break 'digit;
+            }
+            let range: Vec<usize> = input.splitn(2, '-').filter_map(|n| n.trim().parse::<usize>().ok()).collect();
             match input.parse::<usize>() {
                 Ok(value) => {
-                    num_of_digits = value;
+                    num_of_digits = value..=value;
                     break 'digit;
                 },
+                Err(_) if range.len() == 2 => {
+                    if range[0] <= range[1] {
+                        num_of_digits = range[0]..=range[1];
+                        break 'digit;
+                    }
+                    println!("Invalid range, the shortest length goes first.");
+                    input.clear();
+                },
+                Err(_) if input.contains('?') || input.contains('[') => {
+                    match Mask::parse(&input) {
+                        Ok(value) => {
+                            mask = Some(value);
+                            break 'digit;
+                        },
+                        Err(message) => {
+                            println!("{}", message);
+                            input.clear();
+                        },
+                    };
+                },
                 Err(_) => {
                     println!("Invalid digit, try again.");
                     input.clear();
                 },
             };
         }
-        let mut passcode_attempt = Passcode::new(num_of_digits);
-        while passcode_attempt.solutions_left() > 1 {
-            println!("Solutions left: {}", passcode_attempt.solutions_left());
-            println!("Hint: ");
+        let attempt = match mask {
+            None if pattern => Ok(Passcode::pattern()),
+            None if dial => {
+                let size = match prompt_number("Numbers on the dial (blank for 40): ", 40) {
+                    Some(value) => value.max(2),
+                    None => break 'main,
+                };
+                let length = match prompt_number("Numbers in the combination (blank for 3): ", 3) {
+                    Some(value) => value,
+                    None => break 'main,
+                };
+                let tolerance = match prompt_number("Tolerance either side (blank for 1): ", 1) {
+                    Some(value) => value.min(size / 2) as u8,
+                    None => break 'main,
+                };
+                let dial = Alphabet::dial(size).map_err(Error::from);
+                dial.and_then(|alphabet| Passcode::dial(length, alphabet, tolerance)).map(|mut passcode| {
+                    passcode.collapse_within_tolerance(tolerance > 0);
+                    passcode
+                })
+            },
+            None if buttons => {
+                let labels;
+                'labels: loop {
+                    println!("Button labels (blank for 12345): ");
+                    input.clear();
+                    io::stdin().read_line(&mut input).ok();
+                    input = input.trim().to_owned();
+                    let attempt = match input.as_str() {
+                        "q" => break 'main,
+                        "" => Alphabet::from_chars("12345"),
+                        symbols if symbols.contains(' ') => Alphabet::new(&symbols.split_whitespace().collect::<Vec<&str>>()),
+                        symbols => Alphabet::from_chars(symbols),
+                    };
+                    match attempt {
+                        Ok(value) => {
+                            labels = value;
+                            break 'labels;
+                        },
+                        Err(message) => println!("{}", message),
+                    };
+                }
+                println!("Does the order of presses matter? (y/n): ");
+                input.clear();
+                io::stdin().read_line(&mut input).ok();
+                if input.trim() == "n" {
+                    Passcode::button_set(labels)
+                } else {
+                    Passcode::button_sequence(labels)
+                }
+            },
+            Some(mask) => {
+                match mask.size() {
+                    Some(size) => println!("Search space: {}", size),
+                    None => println!("Search space: too large to count"),
+                };
+                Passcode::from_mask(mask)
+            },
+            None => {
+                let alphabet;
+                'alphabet: loop {
+                    println!("Symbols (blank for 0-9, 'hex', 'letters', or the symbols themselves): ");
+                    input.clear();
+                    io::stdin().read_line(&mut input).ok();
+                    input = input.trim().to_owned();
+                    let attempt = match input.as_str() {
+                        "q" => break 'main,
+                        "" => Ok(Alphabet::decimal()),
+                        "hex" => Ok(Alphabet::hexadecimal()),
+                        "letters" => Ok(Alphabet::lowercase()),
+                        symbols if symbols.contains(' ') => Alphabet::new(&symbols.split_whitespace().collect::<Vec<&str>>()),
+                        symbols => Alphabet::from_chars(symbols),
+                    };
+                    match attempt {
+                        Ok(value) => {
+                            alphabet = value;
+                            break 'alphabet;
+                        },
+                        Err(message) => println!("{}", message),
+                    };
+                }
+                Passcode::with_lengths(num_of_digits, alphabet)
+            },
+        };
+        let mut passcode_attempt = match attempt {
+            Ok(passcode) => passcode,
+            Err(message) => {
+                println!("{}", message);
+                continue 'main;
+            },
+        };
+        let enumerable = passcode_attempt.search_space().filter(|size| *size <= ENUMERATION_LIMIT);
+        let backtrack = enumerable.is_none();
+        let guessable = enumerable.is_some_and(|size| size <= CODEBREAK_LIMIT);
+        if guessable && matches!(*passcode_attempt.lock(), Lock::Code | Lock::Dial { .. }) {
+            println!("Should I guess, with you telling me how close each guess is? (y/n, blank for n): ");
+            input.clear();
+            io::stdin().read_line(&mut input).ok();
+            if input.trim() == "y" {
+                match break_code(&mut passcode_attempt) {
+                    Some(solution) => println!("Solution: {}", passcode_attempt.format(&solution)),
+                    None => break 'main,
+                }
+                continue 'main;
+            }
+        }
+        loop {
+            let (left, at_least) = if backtrack {
+                match passcode_attempt.factor() {
+                    Some(factored) => (factored.count(), false),
+                    None => {
+                        let found = passcode_attempt.search().take(COUNT_LIMIT).count();
+                        (found as u128, found == COUNT_LIMIT)
+                    },
+                }
+            } else {
+                (passcode_attempt.solutions_left() as u128, false)
+            };
+            if left == 1 {
+                break;
+            }
+            if left == 0 {
+                match take_back_conflict(&mut passcode_attempt) {
+                    Some(true) => {
+                        if backtrack {
+                            passcode_attempt.propagate();
+                        } else {
+                            passcode_attempt.eliminate_combinations().ok();
+                        }
+                        continue;
+                    },
+                    Some(false) => {},
+                    None => break 'main,
+                }
+            } else if at_least {
+                println!("Solutions left: at least {}", left);
+            } else {
+                println!("Solutions left: {}", left);
+            }
+            println!("Hint ('rules' to list them, 'report' to see what's left, 'ask' for questions worth asking, 'remove' one by number, 'undo', 'redo', or 'why' and a combination): ");
             input.clear();
             io::stdin().read_line(&mut input).ok();
             input = input.trim().to_owned();
             if input == "q" {
                 break 'main;
             }
-            match passcode_attempt.add_rule(&input) {
+            let change = match input.as_str() {
+                "rules" => {
+                    for (id, rule) in passcode_attempt.rules() {
+                        println!("  {}: {}", id, rule);
+                    }
+                    continue;
+                },
+                "report" => {
+                    if backtrack {
+                        println!("Too many combinations are left to report on.");
+                    } else {
+                        passcode_attempt.print_report(REPORT_TOP);
+                    }
+                    continue;
+                },
+                "ask" => {
+                    if backtrack {
+                        println!("Too many combinations are left to pick questions for.");
+                        continue;
+                    }
+                    for query in passcode_attempt.suggest_queries(SUGGESTIONS) {
+                        match query.if_not {
+                            Some(if_not) => println!("  {} (if not, {}): {:.2} bits", query.rule, if_not, query.gain),
+                            None => println!("  {}: {:.2} bits", query.rule, query.gain),
+                        }
+                    }
+                    continue;
+                },
+                "undo" => passcode_attempt.undo().ok_or_else(|| "Nothing to undo.".to_owned()),
+                "redo" => passcode_attempt.redo().ok_or_else(|| "Nothing to redo.".to_owned()),
+                command if command.starts_with("why ") => {
+                    match passcode_attempt.parse(&command["why ".len()..]) {
+                        Ok(combination) => explain(&passcode_attempt, &combination),
+                        Err(message) => println!("{}", message),
+                    };
+                    continue;
+                },
+                command if command.starts_with("remove ") => match command["remove ".len()..].trim().parse::<usize>() {
+                    Ok(id) => passcode_attempt.remove_rule(id).map(|_| id).map_err(|error| error.to_string()),
+                    Err(_) => Err("Give the number of the rule to remove, as listed by 'rules'.".to_owned()),
+                },
+                // The rule was just typed in, so only what's wrong with it is shown.
+                rule => passcode_attempt.add_rule(rule).map_err(|error| match error {
+                    Error::Rule { error, .. } => error.to_string(),
+                    error => error.to_string(),
+                }),
+            };
+            match change {
                 Ok(_) => {
-                    passcode_attempt.eliminate_combinations();
+                    if backtrack {
+                        passcode_attempt.propagate();
+                    } else {
+                        passcode_attempt.eliminate_combinations().ok();
+                    }
+                    if backtrack {
+                        print_domains(&passcode_attempt, passcode_attempt.domains());
+                    } else if let Some(facts) = passcode_attempt.forced_facts() {
+                        print_domains(&passcode_attempt, &facts.domains);
+                        print_facts(&passcode_attempt, facts);
+                    }
                 },
                 Err(message) => {
-                    io::stdout().write(message.as_bytes()).ok();
-                    io::stdout().write(b"\n").ok();
+                    io::stdout().write_all(message.as_bytes()).ok();
+                    io::stdout().write_all(b"\n").ok();
                 },
             };
         }
 
-        println!("Solution: {:?}", passcode_attempt.solution().unwrap());
+        let solution = if backtrack {
+            passcode_attempt.search().next().unwrap()
+        } else {
+            passcode_attempt.solution().unwrap()
+        };
+        println!("Solution: {}", passcode_attempt.format(&solution));
+    }
+}
+
+/// Prints the symbols `domains` has for each position, or `any` if it
+/// can still hold every symbol of the alphabet and `none` if it can't
+/// hold any. Push-button locks are skipped, as their positions are
+/// buttons rather than symbols.
+fn print_domains(passcode: &Passcode, domains: &[Vec<u8>]) {
+    if let Lock::PushButton { .. } = *passcode.lock() {
+        return;
+    }
+    let alphabet = passcode.alphabet();
+    for (i, domain) in domains.iter().enumerate() {
+        let position = position_name(i);
+        let symbols: Vec<&str> = domain.iter().filter_map(|v| alphabet.symbol_for(*v)).collect();
+        if symbols.len() == alphabet.len() {
+            println!("  {}: any", position);
+        } else if symbols.is_empty() {
+            println!("  {}: none", position);
+        } else {
+            println!("  {}: {}", position, symbols.join(" "));
+        }
+    }
+}
+
+/// Prints what every candidate left has in common besides the symbols
+/// each position can hold: the positions that are fixed or always equal
+/// to each other, and the properties that always hold. Positions of
+/// push-button locks are buttons, which the lock's predicates cover.
+fn print_facts(passcode: &Passcode, facts: Facts) {
+    let alphabet = passcode.alphabet();
+    if !matches!(*passcode.lock(), Lock::PushButton { .. }) {
+        for (position, value) in facts.fixed {
+            println!("  {} is always {}", position_name(position), alphabet.symbol_for(value).unwrap_or("?"));
+        }
+        for (first, second) in facts.equal {
+            println!("  {} always equals {}", position_name(second), position_name(first));
+        }
+    }
+    for property in facts.properties {
+        match property {
+            Property::Distinct => println!("  No symbol appears twice"),
+            Property::Increasing => println!("  Every symbol is greater than the one before it"),
+            Property::Decreasing => println!("  Every symbol is less than the one before it"),
+            Property::Predicate(predicate) => println!("  {} always holds", predicate),
+        }
     }
 }
 
+/// Plays the codebreaker: proposes guesses and reads how close each
+/// one is, until the code is found. Feedback that no code fits is
+/// turned down, along with the earlier feedback it contradicts, and
+/// 'undo' takes back the feedback of the guess before. Returns the
+/// code, or `None` if the user quits.
+fn break_code(passcode: &mut Passcode) -> Option<Vec<u8>> {
+    let strategy = loop {
+        println!("Strategy ('minimax', 'expected' or 'parts', blank for minimax): ");
+        let mut input = String::new();
+        io::stdin().read_line(&mut input).ok();
+        match input.trim() {
+            "q" => return None,
+            "" | "minimax" => break Strategy::Minimax,
+            "expected" => break Strategy::ExpectedSize,
+            "parts" => break Strategy::MostParts,
+            _ => println!("Invalid strategy, try again."),
+        }
+    };
+    let mut input = String::new();
+    passcode.eliminate_combinations().ok();
+    loop {
+        if let Ok(solution) = passcode.solution() {
+            return Some(solution);
+        }
+        let guess = passcode.next_guess(strategy)?;
+        let left = passcode.solutions_left();
+        let outlook = if left <= OUTLOOK_LIMIT { passcode.guesses_left(strategy) } else { None };
+        match outlook {
+            Some(outlook) => println!("Solutions left: {}, found in at most {} more guesses, {:.1} on average", left, outlook.worst, outlook.average),
+            None => println!("Solutions left: {}", left),
+        }
+        println!("Guess: {}", passcode.format(&guess));
+        println!("Right symbols at the right place and at the wrong place, such as '1 2' ('undo' to take back the last feedback): ");
+        input.clear();
+        if io::stdin().read_line(&mut input).unwrap_or(0) == 0 {
+            return None;
+        }
+        let given = match input.trim() {
+            "q" => return None,
+            "undo" => {
+                // Each feedback is added as two rules.
+                if passcode.undo().is_some() && passcode.undo().is_some() {
+                    passcode.eliminate_combinations().ok();
+                } else {
+                    println!("Nothing to undo.");
+                }
+                continue;
+            },
+            text => {
+                let counts: Vec<usize> = text.split_whitespace().filter_map(|n| n.parse::<usize>().ok()).collect();
+                match counts[..] {
+                    [exact, misplaced] if exact + misplaced <= guess.len() => Feedback { exact, misplaced },
+                    _ => {
+                        println!("Give two numbers that add up to at most {}.", guess.len());
+                        continue;
+                    },
+                }
+            },
+        };
+        if strategy::is_right(passcode, &guess, given) {
+            return Some(guess);
+        }
+        if let Err(message) = passcode.add_feedback(&guess, given) {
+            println!("{}", message);
+            continue;
+        }
+        passcode.eliminate_combinations().ok();
+        if passcode.solutions_left() == 0 {
+            println!("No code fits that feedback. It contradicts:");
+            let conflict = passcode.conflict().unwrap_or_default();
+            for (id, rule) in passcode.rules() {
+                if conflict.contains(&id) {
+                    println!("  {}", rule);
+                }
+            }
+            passcode.undo();
+            passcode.undo();
+            passcode.eliminate_combinations().ok();
+        }
+    }
+}
+
+/// Shows rules that contradict each other, and offers to take one of
+/// them back. Returns whether one was taken back, or `None` if the user
+/// quits.
+fn take_back_conflict(passcode: &mut Passcode) -> Option<bool> {
+    let conflict = passcode.conflict().unwrap_or_default();
+    println!("No combination satisfies every rule. These rules contradict each other:");
+    for (id, rule) in passcode.rules() {
+        if conflict.contains(&id) {
+            println!("  {}: {}", id, rule);
+        }
+    }
+    let mut input = String::new();
+    loop {
+        println!("Rule to take back (blank to keep them all): ");
+        input.clear();
+        io::stdin().read_line(&mut input).ok();
+        match input.trim() {
+            "q" => return None,
+            "" => return Some(false),
+            value => match value.parse::<usize>() {
+                Ok(id) if conflict.contains(&id) => {
+                    passcode.remove_rule(id).ok();
+                    return Some(true);
+                },
+                _ => println!("Give the number of one of the rules above."),
+            },
+        }
+    }
+}
+
+/// Prints the rules a combination breaks, with the value every part of
+/// each rule came to.
+fn explain(passcode: &Passcode, combination: &[u8]) {
+    let failures = passcode.explain(combination);
+    if failures.is_empty() {
+        println!("{} doesn't break any rule.", passcode.format(combination));
+    }
+    for failure in failures {
+        println!("{} breaks rule {}: {}", passcode.format(combination), failure.id, failure.rule);
+        for line in failure.annotated.to_string().lines() {
+            println!("    {}", line);
+        }
+    }
+}
+
+/// Asks for a number until one is given, returning `default` for a
+/// blank answer and `None` if the user quits.
+fn prompt_number(prompt: &str, default: usize) -> Option<usize> {
+    let mut input = String::new();
+    loop {
+        println!("{}", prompt);
+        input.clear();
+        io::stdin().read_line(&mut input).ok();
+        match input.trim() {
+            "q" => return None,
+            "" => return Some(default),
+            value => match value.parse::<usize>() {
+                Ok(number) => return Some(number),
+                Err(_) => println!("Invalid number, try again."),
+            },
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use alphabet::Alphabet;
+use error::MaskError;
+
+/// A hashcat style description of which symbols each position of a
+/// passcode may hold. Every position is one of:
+/// * a built-in charset: `?d` digits, `?l` lowercase letters, `?u`
+///   uppercase letters, `?h`/`?H` lower/upper hexadecimal digits,
+///   `?s` special characters, `?a` all of the above
+/// * a custom charset in brackets, such as `[0-3]` or `[aeiou]`
+/// * a literal symbol, with `??` standing for a literal `?`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Mask {
+    alphabet: Alphabet,
+    domains: Vec<Vec<u8>>,
+}
+
+impl Mask {
+    /// Parses a mask using the printable ASCII alphabet.
+    ///
+    /// ```
+    /// use passcode_cracker::Mask;
+    ///
+    /// let mask = Mask::parse("?d?d[0-3]?l").unwrap();
+    /// assert_eq!(mask.len(), 4);
+    /// assert_eq!(mask.size(), Some(10 * 10 * 4 * 26));
+    /// ```
+    pub fn parse(mask: &str) -> Result<Mask, MaskError> {
+        Mask::parse_with_alphabet(mask, Alphabet::printable())
+    }
+
+    /// Parses a mask whose symbols all come from the given alphabet.
+    pub fn parse_with_alphabet(mask: &str, alphabet: Alphabet) -> Result<Mask, MaskError> {
+        let mut domains: Vec<Vec<u8>> = Vec::new();
+        let mut chars = mask.chars();
+        while let Some(c) = chars.next() {
+            let symbols: Vec<char> = match c {
+                '?' => match chars.next() {
+                    Some('d') => ('0'..='9').collect(),
+                    Some('l') => ('a'..='z').collect(),
+                    Some('u') => ('A'..='Z').collect(),
+                    Some('h') => ('0'..='9').chain('a'..='f').collect(),
+                    Some('H') => ('0'..='9').chain('A'..='F').collect(),
+                    Some('s') => SPECIAL_CHARACTERS.chars().collect(),
+                    Some('a') => ('a'..='z').chain('A'..='Z').chain('0'..='9').chain(SPECIAL_CHARACTERS.chars()).collect(),
+                    Some('?') => vec!['?'],
+                    Some(other) => return Err(MaskError::UnknownCharset(other)),
+                    None => return Err(MaskError::LoneQuestionMark),
+                },
+                '[' => parse_charset(&mut chars)?,
+                literal => vec![literal],
+            };
+
+            let mut domain: Vec<u8> = Vec::with_capacity(symbols.len());
+            for symbol in symbols {
+                match alphabet.symbol_value(&symbol.to_string()) {
+                    Some(value) => {
+                        if !domain.contains(&value) {
+                            domain.push(value);
+                        }
+                    },
+                    None => return Err(MaskError::NotInAlphabet(symbol)),
+                }
+            }
+            domains.push(domain);
+        }
+
+        if domains.is_empty() {
+            return Err(MaskError::NoPositions);
+        }
+        Ok(Mask { alphabet, domains })
+    }
+
+    pub fn len(&self) -> usize {
+        self.domains.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.domains.is_empty()
+    }
+
+    pub fn alphabet(&self) -> &Alphabet {
+        &self.alphabet
+    }
+
+    /// The symbol values allowed at each position.
+    pub fn domains(&self) -> &[Vec<u8>] {
+        &self.domains
+    }
+
+    /// The number of combinations the mask describes, or `None` if it
+    /// doesn't fit in a `u64`.
+    pub fn size(&self) -> Option<u64> {
+        self.domains.iter().try_fold(1u64, |acc, d| acc.checked_mul(d.len() as u64))
+    }
+}
+
+pub const SPECIAL_CHARACTERS: &str = " !\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+
+fn parse_charset<I: Iterator<Item = char>>(chars: &mut I) -> Result<Vec<char>, MaskError> {
+    let mut symbols: Vec<char> = Vec::new();
+    let mut previous: Option<char> = None;
+    loop {
+        match chars.next() {
+            Some(']') => break,
+            Some('-') if previous.is_some() => {
+                let start = previous.unwrap();
+                let end = match chars.next() {
+                    Some(']') | None => return Err(MaskError::UnfinishedRange),
+                    Some(end) => end,
+                };
+                if end < start {
+                    return Err(MaskError::BackwardsRange(start, end));
+                }
+                symbols.extend((start..=end).skip(1));
+                previous = None;
+            },
+            Some(c) => {
+                symbols.push(c);
+                previous = Some(c);
+            },
+            None => return Err(MaskError::UnterminatedCharset),
+        }
+    }
+    if symbols.is_empty() {
+        return Err(MaskError::EmptyCharset);
+    }
+    Ok(symbols)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_charsets() {
+        let mask = Mask::parse("?d[0-3]x??").unwrap();
+        assert_eq!(mask.domains()[0], (0..10).collect::<Vec<u8>>());
+        assert_eq!(mask.domains()[1], vec![0, 1, 2, 3]);
+        assert_eq!(mask.domains()[2].len(), 1);
+        assert_eq!(mask.domains()[3].len(), 1);
+        assert_eq!(mask.size(), Some(40));
+    }
+
+    #[test]
+    fn parse_custom_alphabet() {
+        let mask = Mask::parse_with_alphabet("?h[a-c]", Alphabet::hexadecimal()).unwrap();
+        assert_eq!(mask.domains()[0].len(), 16);
+        assert_eq!(mask.domains()[1], vec![10, 11, 12]);
+        assert!(Mask::parse_with_alphabet("?l", Alphabet::hexadecimal()).is_err());
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Mask::parse("?d?").is_err());
+        assert!(Mask::parse("?x").is_err());
+        assert!(Mask::parse("[0-3").is_err());
+        assert!(Mask::parse("[3-0]").is_err());
+        assert!(Mask::parse("").is_err());
+    }
+}
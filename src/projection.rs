@@ -0,0 +1,136 @@
+use ast::expression::Expression;
+use ast::bytecode::Program;
+use batch::Batch;
+
+/// A rule's outcome for every assignment of just the positions it
+/// reads, so that checking a candidate against it is a table lookup
+/// rather than an evaluation. The table is indexed by the values at
+/// those positions, as the digits of a number in base `radix`.
+pub struct Projection {
+    positions: Vec<usize>,
+    radix: usize,
+    table: Vec<bool>,
+}
+
+impl Projection {
+    /// Tabulates a rule over values below `radix`, unless it needs the
+    /// whole combination or its table would have more than `limit`
+    /// entries.
+    pub fn new(rule: &Expression, radix: usize, limit: usize) -> Option<Projection> {
+        let mut positions = rule.positions()?;
+        positions.sort_unstable();
+        positions.dedup();
+        let size = positions.iter().try_fold(1usize, |size, _| size.checked_mul(radix)).filter(|size| *size <= limit)?;
+
+        let mut combination: Vec<u8> = vec![0; positions.last().map_or(0, |p| p + 1)];
+        let table = (0..size).map(|index| {
+            let mut rest = index;
+            for position in positions.iter() {
+                combination[*position] = (rest % radix) as u8;
+                rest /= radix;
+            }
+            rule.holds(&combination)
+        }).collect();
+        Some(Projection { positions, radix, table })
+    }
+
+    /// Clears the flags of the candidates of a batch the rule doesn't
+    /// hold for, which like the rule itself includes those missing any
+    /// of its positions.
+    pub fn holds_batch(&self, batch: &Batch, keep: &mut [bool]) {
+        let n = batch.len();
+        let last = match self.positions.last() {
+            Some(&last) => last,
+            None => {
+                if !self.table[0] {
+                    keep.iter_mut().for_each(|keep| *keep = false);
+                }
+                return;
+            },
+        };
+        if last >= batch.columns.len() {
+            keep.iter_mut().for_each(|keep| *keep = false);
+            return;
+        }
+        let mut indices: Vec<usize> = vec![0; n];
+        for position in self.positions.iter().rev() {
+            for (index, value) in indices.iter_mut().zip(batch.columns[*position][..n].iter()) {
+                *index = *index * self.radix + *value as usize;
+            }
+        }
+        for ((keep, index), length) in keep.iter_mut().zip(indices.iter()).zip(batch.lengths[..n].iter()) {
+            *keep &= (*length as usize) > last && self.table[*index];
+        }
+    }
+}
+
+/// One rule, ready to check candidates with.
+pub enum Check<'a> {
+    Lookup(Projection),
+    Run(&'a Program),
+}
+
+impl<'a> Check<'a> {
+    pub fn holds_batch(&self, batch: &Batch, keep: &mut [bool]) {
+        match *self {
+            Check::Lookup(ref projection) => projection.holds_batch(batch, keep),
+            Check::Run(program) => program.holds_batch(batch, keep),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast::parse_code;
+    use space::Space;
+    use batch::BATCH_SIZE;
+
+    /// Checks each combination with a batch of its own.
+    fn holds(projection: &Projection, combination: &[u8]) -> bool {
+        let mut batch = Batch::new(4);
+        Space::table(&[combination.to_vec()]).decode_batch(&[0], &mut batch);
+        let mut keep = [true];
+        projection.holds_batch(&batch, &mut keep);
+        keep[0]
+    }
+
+    fn rule(rule: &str) -> Expression {
+        parse_code(rule, 4)
+    }
+
+    #[test]
+    fn lookup_matches_evaluation() {
+        for text in ["a + c = 9", "d * b > 20", "b - b = 0", "c % 3 = 1", "2 = 2"].iter() {
+            let rule = rule(text);
+            let projection = Projection::new(&rule, 10, 1000).unwrap();
+            let space = Space::domains(&vec![(0..10).collect(); 4], 3);
+            let indices: Vec<u64> = (0..space.size().unwrap()).collect();
+            let mut combination = Vec::new();
+            for chunk in indices.chunks(BATCH_SIZE) {
+                let mut batch = Batch::new(4);
+                space.decode_batch(chunk, &mut batch);
+                let mut keep = vec![true; chunk.len()];
+                projection.holds_batch(&batch, &mut keep);
+                for (index, kept) in chunk.iter().zip(keep.iter()) {
+                    space.decode(*index, &mut combination);
+                    assert_eq!(*kept, rule.holds(&combination), "{} on {:?}", text, combination);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn missing_positions() {
+        let projection = Projection::new(&rule("d > 3"), 10, 1000).unwrap();
+        assert!(holds(&projection, &[0, 0, 0, 4]));
+        assert!(!holds(&projection, &[0, 0, 4]));
+    }
+
+    #[test]
+    fn too_large_or_whole_combination() {
+        assert!(Projection::new(&rule("a + b + c = 9"), 10, 100).is_none());
+        assert!(Projection::new(&rule("len = 4"), 10, 1000).is_none());
+        assert!(Projection::new(&rule("d[-1] = 4"), 10, 1000).is_none());
+    }
+}
@@ -0,0 +1,233 @@
+use ast::expression::Expression;
+use ast::operator::{Operator, BinaryOperator, BooleanOperator};
+use ast::evaluable::Value;
+
+/// The smallest and largest value an expression can take.
+type Bounds = (i64, i64);
+
+/// How the two sides of a comparison relate.
+enum Relation {
+    Equal,
+    LessThan,
+    GreaterThan,
+}
+
+/// Removes the values from each position's domain that can't be part
+/// of any combination satisfying every rule, until no rule can remove
+/// any more. Rules over one or two positions are checked against every
+/// value left in those positions. Longer rules are reasoned about
+/// through the bounds of their sides, which can't see through `%` or
+/// dial arithmetic. Rules that need the whole combination are left to
+/// enumeration. Returns whether any domain shrank.
+pub fn propagate(domains: &mut [Vec<u8>], rules: &[Expression]) -> bool {
+    let scopes: Vec<Option<Vec<usize>>> = rules.iter().map(|rule| rule.positions().map(|mut positions| {
+        positions.sort_unstable();
+        positions.dedup();
+        positions
+    })).collect();
+
+    let mut shrunk = false;
+    loop {
+        let mut changed = false;
+        for (rule, scope) in rules.iter().zip(scopes.iter()) {
+            let scope = match *scope {
+                Some(ref scope) if !scope.is_empty() && scope[scope.len() - 1] < domains.len() => scope,
+                _ => continue,
+            };
+            if domains.iter().any(|d| d.is_empty()) {
+                return true;
+            }
+            changed |= if scope.len() <= 2 {
+                revise_exactly(rule, scope, domains)
+            } else {
+                revise_bounds(rule, domains)
+            };
+        }
+        if !changed {
+            return shrunk;
+        }
+        shrunk = true;
+    }
+}
+
+/// Keeps the values of each position in `scope` that the rule holds for
+/// with some value of the other position.
+fn revise_exactly(rule: &Expression, scope: &[usize], domains: &mut [Vec<u8>]) -> bool {
+    let mut combination: Vec<u8> = vec![0; scope[scope.len() - 1] + 1];
+    let mut changed = false;
+    for (i, &position) in scope.iter().enumerate() {
+        let other = if scope.len() == 2 { Some(scope[1 - i]) } else { None };
+        let supported: Vec<u8> = domains[position].iter().cloned().filter(|&value| {
+            combination[position] = value;
+            match other {
+                Some(other) => domains[other].iter().any(|&o| {
+                    combination[other] = o;
+                    rule.holds(&combination)
+                }),
+                None => rule.holds(&combination),
+            }
+        }).collect();
+        if supported.len() < domains[position].len() {
+            domains[position] = supported;
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Narrows both sides of a comparison to the bounds the other side
+/// leaves them.
+fn revise_bounds(rule: &Expression, domains: &mut [Vec<u8>]) -> bool {
+    let (comparison, relation) = match *rule {
+        Expression::BinaryExp(ref comparison) => match comparison.operator {
+            Operator::Boolean(BooleanOperator::Equal) => (comparison, Relation::Equal),
+            Operator::Boolean(BooleanOperator::LessThan) => (comparison, Relation::LessThan),
+            Operator::Boolean(BooleanOperator::GreaterThan) => (comparison, Relation::GreaterThan),
+            _ => return false,
+        },
+        _ => return false,
+    };
+    let (l, r) = match (bounds(&comparison.l_value, domains), bounds(&comparison.r_value, domains)) {
+        (Some(l), Some(r)) => (l, r),
+        _ => return false,
+    };
+    let (l_target, r_target) = match relation {
+        Relation::Equal => ((l.0.max(r.0), l.1.min(r.1)), (l.0.max(r.0), l.1.min(r.1))),
+        Relation::LessThan => ((l.0, r.1.saturating_sub(1)), (l.0.saturating_add(1), r.1)),
+        Relation::GreaterThan => ((r.0.saturating_add(1), l.1), (r.0, l.1.saturating_sub(1))),
+    };
+    narrow(&comparison.l_value, l_target, domains) | narrow(&comparison.r_value, r_target, domains)
+}
+
+/// The bounds of a numerical expression, if they can be worked out.
+fn bounds(expression: &Expression, domains: &[Vec<u8>]) -> Option<Bounds> {
+    match *expression {
+        Expression::Variable(ref var) => {
+            let domain = domains.get(var.index)?;
+            Some((*domain.iter().min()? as i64, *domain.iter().max()? as i64))
+        },
+        Expression::Value(Value::Numerical(n)) => Some((n, n)),
+        Expression::BinaryExp(ref bin_exp) => {
+            if let Some(modulus) = bin_exp.modulus {
+                return Some((0, modulus - 1));
+            }
+            let op = match bin_exp.operator {
+                Operator::Binary(ref op) => op,
+                _ => return None,
+            };
+            let l = bounds(&bin_exp.l_value, domains)?;
+            let r = bounds(&bin_exp.r_value, domains)?;
+            match *op {
+                BinaryOperator::Add => Some((l.0.checked_add(r.0)?, l.1.checked_add(r.1)?)),
+                BinaryOperator::Subtract => Some((l.0.checked_sub(r.1)?, l.1.checked_sub(r.0)?)),
+                BinaryOperator::Multiply => {
+                    let products = [l.0.checked_mul(r.0)?, l.0.checked_mul(r.1)?, l.1.checked_mul(r.0)?, l.1.checked_mul(r.1)?];
+                    Some((*products.iter().min()?, *products.iter().max()?))
+                },
+                BinaryOperator::Modulo if r.0 == r.1 && r.0 > 0 => Some((0, r.0 - 1)),
+                BinaryOperator::Modulo => None,
+            }
+        },
+        _ => None,
+    }
+}
+
+/// Narrows the positions an expression reads so that it can only take
+/// values within `target`.
+fn narrow(expression: &Expression, target: Bounds, domains: &mut [Vec<u8>]) -> bool {
+    match *expression {
+        Expression::Variable(ref var) => {
+            let domain = &mut domains[var.index];
+            let before = domain.len();
+            domain.retain(|&v| target.0 <= v as i64 && v as i64 <= target.1);
+            domain.len() < before
+        },
+        Expression::BinaryExp(ref bin_exp) if bin_exp.modulus.is_none() => {
+            let op = match bin_exp.operator {
+                Operator::Binary(ref op) => op,
+                _ => return false,
+            };
+            let (l, r) = match (bounds(&bin_exp.l_value, domains), bounds(&bin_exp.r_value, domains)) {
+                (Some(l), Some(r)) => (l, r),
+                _ => return false,
+            };
+            let (lo, hi) = target;
+            let (l_target, r_target) = match *op {
+                BinaryOperator::Add => (
+                    Some((lo.saturating_sub(r.1), hi.saturating_sub(r.0))),
+                    Some((lo.saturating_sub(l.1), hi.saturating_sub(l.0))),
+                ),
+                BinaryOperator::Subtract => (
+                    Some((lo.saturating_add(r.0), hi.saturating_add(r.1))),
+                    Some((l.0.saturating_sub(hi), l.1.saturating_sub(lo))),
+                ),
+                BinaryOperator::Multiply => (quotient(target, r), quotient(target, l)),
+                BinaryOperator::Modulo => (None, None),
+            };
+            let mut changed = false;
+            if let Some(l_target) = l_target {
+                changed |= narrow(&bin_exp.l_value, l_target, domains);
+            }
+            if let Some(r_target) = r_target {
+                changed |= narrow(&bin_exp.r_value, r_target, domains);
+            }
+            changed
+        },
+        _ => false,
+    }
+}
+
+/// The bounds of `x` given that `x * y` lies within `product`, when `y`
+/// is known to be positive.
+fn quotient(product: Bounds, divisor: Bounds) -> Option<Bounds> {
+    if divisor.0 < 1 {
+        return None;
+    }
+    let floor = |n: i64, d: i64| n.div_euclid(d);
+    let ceil = |n: i64, d: i64| if n.rem_euclid(d) == 0 { n.div_euclid(d) } else { n.div_euclid(d) + 1 };
+    Some((
+        ceil(product.0, divisor.0).min(ceil(product.0, divisor.1)),
+        floor(product.1, divisor.0).max(floor(product.1, divisor.1)),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast::parse_code;
+
+    fn reduce(rules: &[&str], length: usize) -> Vec<Vec<u8>> {
+        let rules: Vec<Expression> = rules.iter().map(|rule| parse_code(rule, length)).collect();
+        let mut domains = vec![(0..10).collect(); length];
+        propagate(&mut domains, &rules);
+        domains
+    }
+
+    #[test]
+    fn binary_rules() {
+        assert_eq!(reduce(&["a + b = 17"], 2), vec![vec![8, 9], vec![8, 9]]);
+        assert_eq!(reduce(&["a % 4 = 3", "a > b"], 2), vec![vec![3, 7], (0..7).collect()]);
+    }
+
+    #[test]
+    fn bounds_reasoning() {
+        let domains = reduce(&["a + b + c = 26"], 3);
+        assert_eq!(domains, vec![vec![8, 9]; 3]);
+        let domains = reduce(&["a + b + c = 26", "a * b * c > 700"], 3);
+        assert!(domains.iter().any(|d| d.is_empty()));
+        let domains = reduce(&["a - b - c > 6"], 3);
+        assert_eq!(domains, vec![vec![7, 8, 9], vec![0, 1, 2], vec![0, 1, 2]]);
+    }
+
+    #[test]
+    fn chains_through_positions() {
+        let domains = reduce(&["a < b", "b < c", "c < d", "d < 4"], 4);
+        assert_eq!(domains, vec![vec![0], vec![1], vec![2], vec![3]]);
+    }
+
+    #[test]
+    fn leaves_whole_combination_rules_alone() {
+        let domains = reduce(&["d[-1] = 3", "len = 2"], 2);
+        assert_eq!(domains, vec![(0..10).collect::<Vec<u8>>(); 2]);
+    }
+}
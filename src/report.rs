@@ -0,0 +1,103 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// How the candidates still possible are spread out: how often each
+/// value appears at each position, and the candidates most typical of
+/// the rest.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Report {
+    /// How many candidates the report covers.
+    pub total: usize,
+    /// For each position, every value it holds in some candidate along
+    /// with how many candidates hold it there, in increasing order of
+    /// value.
+    pub frequencies: Vec<Vec<(u8, usize)>>,
+    /// The candidates that share the most values with the rest,
+    /// position by position, most typical first. Each comes with the
+    /// number of values it shares: the sum, over its positions, of how
+    /// many candidates hold the same value there.
+    pub most_common: Vec<(Vec<u8>, usize)>,
+}
+
+impl Report {
+    /// Goes through the candidates `candidates` gives, none of which
+    /// are longer than `max_length`, twice: once to count the values at
+    /// each position and once to find the `top` most typical.
+    pub fn gather<F, I>(candidates: F, max_length: usize, top: usize) -> Report
+        where F: Fn() -> I, I: Iterator<Item = Vec<u8>>
+    {
+        let mut counts = vec![vec![0usize; 256]; max_length];
+        let mut total = 0;
+        for candidate in candidates() {
+            for (position, value) in candidate.iter().enumerate() {
+                counts[position][*value as usize] += 1;
+            }
+            total += 1;
+        }
+        // The least typical candidate kept so far is on top, and among
+        // equally typical ones the latest, so earlier candidates win ties.
+        let mut kept = BinaryHeap::with_capacity(top + 1);
+        if top > 0 {
+            for (i, candidate) in candidates().enumerate() {
+                let shared = candidate.iter().enumerate().map(|(position, value)| counts[position][*value as usize]).sum();
+                kept.push(Reverse((shared, Reverse(i), candidate)));
+                if kept.len() > top {
+                    kept.pop();
+                }
+            }
+        }
+        let most_common = kept.into_sorted_vec().into_iter().map(|Reverse((shared, _, candidate))| (candidate, shared)).collect();
+        let frequencies = counts.iter().map(|counts| {
+            counts.iter().enumerate().filter(|&(_, count)| *count > 0).map(|(value, count)| (value as u8, *count)).collect()
+        }).collect();
+        Report { total, frequencies, most_common }
+    }
+
+    /// Lays the frequencies out as a table with a row per position and
+    /// a column per value in `values`, giving the percentage of the
+    /// candidates that hold the value there, or `.` if none do. Rows are
+    /// headed by `rows` and columns by `column` of their value.
+    pub fn table<C>(&self, rows: &[String], values: &[u8], column: C) -> String
+        where C: Fn(u8) -> String
+    {
+        let headings: Vec<String> = values.iter().map(|value| column(*value)).collect();
+        let label_width = rows.iter().map(|row| row.chars().count()).max().unwrap_or(0);
+        let width = headings.iter().map(|heading| heading.chars().count()).max().unwrap_or(0).max(3);
+        let mut table = " ".repeat(label_width);
+        for heading in headings.iter() {
+            table.push_str(&format!(" {:>1$}", heading, width));
+        }
+        table.push('\n');
+        for (row, frequencies) in rows.iter().zip(self.frequencies.iter()) {
+            table.push_str(&format!("{:<1$}", row, label_width));
+            for value in values.iter() {
+                let count = frequencies.iter().find(|&&(v, _)| v == *value).map_or(0, |&(_, count)| count);
+                let cell = if count == 0 {
+                    ".".to_owned()
+                } else {
+                    format!("{}%", (count * 100 + self.total / 2) / self.total)
+                };
+                table.push_str(&format!(" {:>1$}", cell, width));
+            }
+            table.push('\n');
+        }
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_and_most_common() {
+        let candidates = [vec![1, 2], vec![1, 3], vec![2, 3], vec![3, 4]];
+        let report = Report::gather(|| candidates.iter().cloned(), 2, 2);
+        assert_eq!(report.total, 4);
+        assert_eq!(report.frequencies, vec![vec![(1, 2), (2, 1), (3, 1)], vec![(2, 1), (3, 2), (4, 1)]]);
+        assert_eq!(report.most_common, vec![(vec![1, 3], 4), (vec![1, 2], 3)]);
+        let rows = vec!["a".to_owned(), "b".to_owned()];
+        let table = report.table(&rows, &[1, 2, 3, 4], |value| value.to_string());
+        assert_eq!(table, "    1   2   3   4\na 50% 25% 25%   .\nb   . 25% 50% 25%\n");
+    }
+}
@@ -0,0 +1,168 @@
+use ast::expression::Expression;
+
+/// Finds the combinations of a set of domains that satisfy every rule
+/// by assigning positions one at a time, from the first. A rule that
+/// only reads positions is checked as soon as its last position is
+/// assigned, so a broken rule rules out every combination that starts
+/// the same way at once. Rules that need the whole combination, such
+/// as ones using `len` or `d[-1]`, are checked once it's complete.
+///
+/// Solutions come out in the same order `Space::domains` numbers them.
+pub struct Search<'a> {
+    domains: &'a [Vec<u8>],
+    /// The rules to check once the first `i` positions are assigned.
+    checks: Vec<Vec<&'a Expression>>,
+    complete: Vec<&'a Expression>,
+    /// How many positions the rules need at the least.
+    needed: usize,
+    length: usize,
+    fresh: bool,
+    descend: bool,
+    choices: Vec<usize>,
+    prefix: Vec<u8>,
+}
+
+impl<'a> Search<'a> {
+    pub fn new(domains: &'a [Vec<u8>], min_length: usize, rules: &'a [Expression]) -> Search<'a> {
+        let mut checks: Vec<Vec<&Expression>> = vec![Vec::new(); domains.len() + 1];
+        let mut complete: Vec<&Expression> = Vec::new();
+        let mut needed = 0;
+        for rule in rules.iter() {
+            match rule.positions() {
+                Some(positions) => {
+                    let depth = positions.iter().map(|p| p + 1).max().unwrap_or(0);
+                    needed = needed.max(depth);
+                    if depth < checks.len() {
+                        checks[depth].push(rule);
+                    }
+                },
+                None => complete.push(rule),
+            }
+        }
+        Search {
+            domains,
+            checks,
+            complete,
+            needed,
+            length: min_length,
+            fresh: true,
+            descend: true,
+            choices: Vec::new(),
+            prefix: Vec::new(),
+        }
+    }
+
+    /// Moves on to the next value at the deepest position that has one
+    /// left, dropping the positions after it.
+    fn next_sibling(&mut self) -> bool {
+        while let Some(choice) = self.choices.pop() {
+            self.prefix.pop();
+            let depth = self.choices.len();
+            if choice + 1 < self.domains[depth].len() {
+                self.choices.push(choice + 1);
+                self.prefix.push(self.domains[depth][choice + 1]);
+                return true;
+            }
+        }
+        false
+    }
+
+    fn next_length(&mut self) {
+        self.length += 1;
+        self.fresh = true;
+    }
+}
+
+impl<'a> Iterator for Search<'a> {
+    type Item = Vec<u8>;
+    fn next(&mut self) -> Option<Vec<u8>> {
+        loop {
+            if self.length > self.domains.len() {
+                return None;
+            }
+            if self.fresh {
+                self.fresh = false;
+                self.choices.clear();
+                self.prefix.clear();
+                if self.length < self.needed {
+                    self.next_length();
+                    continue;
+                }
+            } else if self.descend && self.prefix.len() < self.length {
+                let depth = self.prefix.len();
+                match self.domains[depth].first() {
+                    Some(&value) => {
+                        self.choices.push(0);
+                        self.prefix.push(value);
+                    },
+                    None => {
+                        self.descend = false;
+                        continue;
+                    },
+                }
+            } else if !self.next_sibling() {
+                self.next_length();
+                continue;
+            }
+
+            let depth = self.prefix.len();
+            let prefix = &self.prefix;
+            self.descend = self.checks[depth].iter().all(|rule| rule.holds(prefix));
+            if self.descend && depth == self.length {
+                self.descend = false;
+                if self.complete.iter().all(|rule| rule.holds(prefix)) {
+                    return Some(self.prefix.clone());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast::parse_code;
+    use space::Space;
+
+    fn rules(rules: &[&str], total_vars: usize) -> Vec<Expression> {
+        rules.iter().map(|rule| parse_code(rule, total_vars)).collect()
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let domains = vec![(0..10).collect::<Vec<u8>>(); 4];
+        let rules = rules(&["a + b = 9", "c > d", "d[-1] = len"], 4);
+        let space = Space::domains(&domains, 2);
+        let mut combination = Vec::new();
+        let expected: Vec<Vec<u8>> = (0..space.size().unwrap()).filter_map(|i| {
+            space.decode(i, &mut combination);
+            if rules.iter().all(|rule| rule.holds(&combination)) { Some(combination.clone()) } else { None }
+        }).collect();
+
+        let found: Vec<Vec<u8>> = Search::new(&domains, 2, &rules).collect();
+        assert_eq!(found, expected);
+        assert_eq!(found.len(), 10 * 5);
+    }
+
+    #[test]
+    fn long_codes() {
+        let domains = vec![(0..10).collect::<Vec<u8>>(); 16];
+        let mut hints = Vec::new();
+        for (position, next) in "abcdefghijklmno".chars().zip("bcdefghijklmnop".chars()) {
+            hints.push(format!("{} = {}", position, next));
+        }
+        hints.push("a > 6".to_owned());
+        let hints: Vec<&str> = hints.iter().map(|h| h.as_str()).collect();
+        let rules = rules(&hints, 16);
+        let found: Vec<Vec<u8>> = Search::new(&domains, 16, &rules).collect();
+        assert_eq!(found, vec![vec![7; 16], vec![8; 16], vec![9; 16]]);
+    }
+
+    #[test]
+    fn skips_lengths_too_short_for_the_rules() {
+        let domains = vec![(0..10).collect::<Vec<u8>>(); 3];
+        let rules = rules(&["c = 1"], 3);
+        assert_eq!(Search::new(&domains, 1, &rules).count(), 100);
+        assert_eq!(Search::new(&domains[..2], 1, &[]).count(), 110);
+    }
+}
@@ -0,0 +1,296 @@
+use batch::Batch;
+
+/// Every candidate a passcode starts out with, numbered from 0 so the
+/// candidates still possible can be tracked by index alone. A candidate
+/// is only decoded back into its symbol values when it's needed.
+pub enum Space {
+    /// Every combination of the domains, one length after another from
+    /// the shortest, each counted off by its own generator.
+    Domains(Vec<CombinationGenerator>),
+    /// An explicit list of combinations, packed into integers.
+    Table(PackedTable),
+}
+
+impl Space {
+    /// Every combination of `domains[..length]` for each length from
+    /// `min_length` up to the number of domains.
+    pub fn domains(domains: &[Vec<u8>], min_length: usize) -> Space {
+        let generators = (min_length..=domains.len()).map(|length| CombinationGenerator::new(domains[..length].to_vec())).collect();
+        Space::Domains(generators)
+    }
+
+    pub fn table(combinations: &[Vec<u8>]) -> Space {
+        Space::Table(PackedTable::new(combinations))
+    }
+
+    /// The number of candidates in the space, or `None` if there are
+    /// more than a `u64` can count.
+    pub fn size(&self) -> Option<u64> {
+        match *self {
+            Space::Domains(ref generators) => generators.iter().try_fold(0u64, |acc, g| acc.checked_add(g.size()?)),
+            Space::Table(ref table) => Some(table.entries.len() as u64),
+        }
+    }
+
+    /// The length of the longest candidate.
+    pub fn max_length(&self) -> usize {
+        match *self {
+            Space::Domains(ref generators) => generators.last().map_or(0, |g| g.domains.len()),
+            Space::Table(ref table) => table.max_length,
+        }
+    }
+
+    /// The largest value any position of any candidate holds.
+    pub fn max_value(&self) -> u8 {
+        match *self {
+            Space::Domains(ref generators) => generators.iter().flat_map(|g| g.domains.iter()).flat_map(|d| d.iter()).cloned().max().unwrap_or(0),
+            Space::Table(ref table) => table.max_value,
+        }
+    }
+
+    /// Whether every combination of `domains[..length]` for each length
+    /// from `min_length` up is one of the space's candidates.
+    pub fn covers(&self, domains: &[Vec<u8>], min_length: usize) -> bool {
+        match *self {
+            Space::Domains(ref generators) => match (generators.first(), generators.last()) {
+                (Some(shortest), Some(longest)) => shortest.domains.len() <= min_length
+                    && longest.domains.len() == domains.len()
+                    && longest.domains.iter().zip(domains.iter()).all(|(have, want)| want.iter().all(|v| have.contains(v))),
+                _ => false,
+            },
+            // Tables are never narrowed down, so they always hold every
+            // candidate the lock has.
+            Space::Table(_) => true,
+        }
+    }
+
+    /// Writes the candidates numbered `indices` into `batch`. Runs of
+    /// consecutive indices are counted off like an odometer instead of
+    /// being decoded one by one.
+    pub fn decode_batch(&self, indices: &[u64], batch: &mut Batch) {
+        batch.set_len(indices.len());
+        match *self {
+            Space::Domains(ref generators) => {
+                let mut digits: Vec<usize> = Vec::new();
+                let mut current: Option<(u64, usize, u64)> = None;
+                for (k, &index) in indices.iter().enumerate() {
+                    let (g, local) = match current {
+                        Some((previous, g, local)) if previous + 1 == index && local + 1 < generators[g].total => {
+                            for digit in (0..digits.len()).rev() {
+                                digits[digit] += 1;
+                                if digits[digit] < generators[g].domains[digit].len() {
+                                    break;
+                                }
+                                digits[digit] = 0;
+                            }
+                            (g, local + 1)
+                        },
+                        _ => {
+                            let mut local = index;
+                            let mut g = 0;
+                            while local >= generators[g].total {
+                                local -= generators[g].total;
+                                g += 1;
+                            }
+                            generators[g].digits(local, &mut digits);
+                            (g, local)
+                        },
+                    };
+                    let domains = &generators[g].domains;
+                    for (p, digit) in digits.iter().enumerate() {
+                        batch.columns[p][k] = domains[p][*digit];
+                    }
+                    batch.lengths[k] = domains.len() as u8;
+                    current = Some((index, g, local));
+                }
+            },
+            Space::Table(ref table) => {
+                let mut buffer: Vec<u8> = Vec::with_capacity(table.max_length);
+                for (k, &index) in indices.iter().enumerate() {
+                    table.decode(index, &mut buffer);
+                    for (p, value) in buffer.iter().enumerate() {
+                        batch.columns[p][k] = *value;
+                    }
+                    batch.lengths[k] = buffer.len() as u8;
+                }
+            },
+        }
+    }
+
+    /// Writes the candidate numbered `index` into `buffer`.
+    pub fn decode(&self, index: u64, buffer: &mut Vec<u8>) {
+        match *self {
+            Space::Domains(ref generators) => {
+                let mut index = index;
+                for generator in generators.iter() {
+                    if index < generator.total {
+                        generator.decode(index, buffer);
+                        return;
+                    }
+                    index -= generator.total;
+                }
+                panic!("Candidate index out of range");
+            },
+            Space::Table(ref table) => table.decode(index, buffer),
+        }
+    }
+}
+
+/// Generates every combination where each position takes one of the
+/// values of its domain, counting like an odometer from the last
+/// position.
+pub struct CombinationGenerator {
+    domains: Vec<Vec<u8>>,
+    /// How many combinations there are, capped at `u64::MAX`.
+    total: u64,
+    count: u64,
+}
+
+impl CombinationGenerator {
+    pub fn new(domains: Vec<Vec<u8>>) -> CombinationGenerator {
+        let total = domains.iter().fold(1u64, |acc, d| acc.saturating_mul(d.len() as u64));
+        CombinationGenerator { domains, total, count: 0 }
+    }
+
+    /// How many combinations there are, or `None` if there are more than
+    /// a `u64` can count.
+    fn size(&self) -> Option<u64> {
+        self.domains.iter().try_fold(1u64, |acc, d| acc.checked_mul(d.len() as u64))
+    }
+
+    /// Writes the combination the generator would produce as its
+    /// `count`th item into `buffer`.
+    fn decode(&self, count: u64, buffer: &mut Vec<u8>) {
+        buffer.clear();
+        buffer.extend(self.domains.iter().map(|d| d[0]));
+        let mut count_copy = count;
+        for i in (0..self.domains.len()).rev() {
+            let base = self.domains[i].len() as u64;
+            buffer[i] = self.domains[i][(count_copy % base) as usize];
+            count_copy /= base;
+            if count_copy == 0 {
+                break;
+            }
+        }
+    }
+
+    /// Writes where in its domain each position of the `count`th
+    /// combination is into `digits`.
+    fn digits(&self, count: u64, digits: &mut Vec<usize>) {
+        digits.clear();
+        digits.resize(self.domains.len(), 0);
+        let mut count_copy = count;
+        for i in (0..self.domains.len()).rev() {
+            let base = self.domains[i].len() as u64;
+            digits[i] = (count_copy % base) as usize;
+            count_copy /= base;
+        }
+    }
+}
+
+impl Iterator for CombinationGenerator {
+    type Item = Vec<u8>;
+    fn next(&mut self) -> Option<Vec<u8>> {
+        if self.count >= self.total {
+            return None;
+        }
+        let mut ret_vec: Vec<u8> = Vec::with_capacity(self.domains.len());
+        self.decode(self.count, &mut ret_vec);
+        self.count += 1;
+        Some(ret_vec)
+    }
+}
+
+/// Combinations packed one per `u64`: the length in the lowest five
+/// bits, followed by each value in `bits` bits.
+pub struct PackedTable {
+    bits: u32,
+    max_value: u8,
+    max_length: usize,
+    entries: Vec<u64>,
+}
+
+const LENGTH_BITS: u32 = 5;
+
+impl PackedTable {
+    fn new(combinations: &[Vec<u8>]) -> PackedTable {
+        let max_value = combinations.iter().flat_map(|c| c.iter()).cloned().max().unwrap_or(0);
+        let bits = 8 - max_value.leading_zeros();
+        let max_length = combinations.iter().map(|c| c.len()).max().unwrap_or(0);
+        assert!(max_length < (1 << LENGTH_BITS) && LENGTH_BITS + max_length as u32 * bits <= 64, "Combinations are too long to pack");
+
+        let entries = combinations.iter().map(|combination| {
+            let values = combination.iter().rev().fold(0u64, |packed, value| (packed << bits) | *value as u64);
+            (values << LENGTH_BITS) | combination.len() as u64
+        }).collect();
+        PackedTable { bits, max_value, max_length, entries }
+    }
+
+    fn decode(&self, index: u64, buffer: &mut Vec<u8>) {
+        let entry = self.entries[index as usize];
+        let length = (entry & ((1 << LENGTH_BITS) - 1)) as usize;
+        let mask = (1u64 << self.bits) - 1;
+        buffer.clear();
+        let mut values = entry >> LENGTH_BITS;
+        for _ in 0..length {
+            buffer.push((values & mask) as u8);
+            values >>= self.bits;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_domains() {
+        let space = Space::domains(&[vec![0, 1], vec![5, 6, 7]], 1);
+        assert_eq!(space.size(), Some(2 + 6));
+        assert_eq!(Space::domains(&vec![(0..10).collect(); 20], 20).size(), None);
+        assert_eq!(space.max_value(), 7);
+        let mut buffer = Vec::new();
+        let decoded: Vec<Vec<u8>> = (0..space.size().unwrap()).map(|i| { space.decode(i, &mut buffer); buffer.clone() }).collect();
+        assert_eq!(decoded, vec![vec![0], vec![1], vec![0, 5], vec![0, 6], vec![0, 7], vec![1, 5], vec![1, 6], vec![1, 7]]);
+    }
+
+    #[test]
+    fn covers_narrower_domains() {
+        let space = Space::domains(&[vec![0, 1, 2], vec![5, 6]], 1);
+        assert!(space.covers(&[vec![0, 2], vec![5, 6]], 1));
+        assert!(space.covers(&[vec![1], vec![6]], 2));
+        assert!(!space.covers(&[vec![0, 3], vec![5]], 1));
+        assert!(!space.covers(&[vec![0], vec![5]], 0));
+        assert!(!Space::domains(&[vec![0, 1, 2], vec![5, 6]], 2).covers(&[vec![0], vec![5]], 1));
+    }
+
+    #[test]
+    fn decode_batches() {
+        let spaces = [Space::domains(&[vec![0, 1], vec![5, 6, 7], vec![2, 3]], 1), Space::table(&[vec![1, 2], vec![3], vec![4, 5, 6]])];
+        for space in spaces.iter() {
+            let mut batch = Batch::new(space.max_length());
+            let indices: Vec<u64> = (0..space.size().unwrap()).filter(|i| i % 4 != 1).collect();
+            space.decode_batch(&indices, &mut batch);
+            assert_eq!(batch.len(), indices.len());
+            let (mut expected, mut row) = (Vec::new(), Vec::new());
+            for (k, index) in indices.iter().enumerate() {
+                space.decode(*index, &mut expected);
+                batch.row(k, &mut row);
+                assert_eq!(row, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn decode_table() {
+        let combinations = vec![vec![1, 2, 3, 4], vec![9, 8, 7, 6, 5, 4, 3, 2, 1], vec![], vec![0, 0]];
+        let space = Space::table(&combinations);
+        assert_eq!(space.size(), Some(4));
+        assert_eq!(space.max_length(), 9);
+        let mut buffer = Vec::new();
+        for (i, combination) in combinations.iter().enumerate() {
+            space.decode(i as u64, &mut buffer);
+            assert_eq!(&buffer, combination);
+        }
+    }
+}
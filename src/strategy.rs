@@ -0,0 +1,236 @@
+use std::collections::{HashMap, HashSet};
+use error::Error;
+use Passcode;
+
+/// How close a guess is to the code, as in Mastermind.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Feedback {
+    /// How many positions of the guess hold the right symbol.
+    pub exact: usize,
+    /// How many more of the guess's symbols are in the code, but at
+    /// another position. Each symbol of the code only counts once.
+    pub misplaced: usize,
+}
+
+/// Scores `guess` against `code`. When they differ in length, the
+/// positions only one of them has can only count as misplaced.
+pub fn feedback(guess: &[u8], code: &[u8]) -> Feedback {
+    // How many times each symbol of the code not matched exactly is left
+    // to be matched by a misplaced one.
+    let mut left = [0u8; 256];
+    let mut exact = 0;
+    for (i, value) in code.iter().enumerate() {
+        if guess.get(i) == Some(value) {
+            exact += 1;
+        } else {
+            left[*value as usize] += 1;
+        }
+    }
+    let mut misplaced = 0;
+    for (i, value) in guess.iter().enumerate() {
+        if code.get(i) != Some(value) && left[*value as usize] > 0 {
+            left[*value as usize] -= 1;
+            misplaced += 1;
+        }
+    }
+    Feedback { exact, misplaced }
+}
+
+/// How to pick the next guess, by how it splits the candidates still
+/// possible into groups that would get the same feedback.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Strategy {
+    /// Knuth's: the guess whose largest group is smallest.
+    Minimax,
+    /// The guess that leaves the fewest candidates on average.
+    ExpectedSize,
+    /// The guess that splits the candidates into the most groups.
+    MostParts,
+}
+
+/// How many more guesses finding the code is going to take, counting the
+/// one that finds it.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Outlook {
+    /// The most guesses any of the candidates takes.
+    pub worst: usize,
+    /// The number of guesses averaged over the candidates.
+    pub average: f64,
+}
+
+/// Picks the guess out of `guesses` that splits `possible` best
+/// according to `strategy`. Among guesses that split them equally well,
+/// ones that could be the code come first, as they might be right, and
+/// then the first given. Returns `None` if there are no guesses.
+pub fn best_guess(guesses: &[Vec<u8>], possible: &[Vec<u8>], strategy: Strategy) -> Option<Vec<u8>> {
+    let possible_set: HashSet<&[u8]> = possible.iter().map(|code| code.as_slice()).collect();
+    guesses.iter().min_by_key(|guess| {
+        let mut sizes: HashMap<Feedback, usize> = HashMap::new();
+        for code in possible {
+            *sizes.entry(feedback(guess, code)).or_insert(0) += 1;
+        }
+        let score = match strategy {
+            Strategy::Minimax => sizes.values().cloned().max().unwrap_or(0),
+            Strategy::ExpectedSize => sizes.values().map(|size| size * size).sum(),
+            Strategy::MostParts => usize::MAX - sizes.len(),
+        };
+        (score, !possible_set.contains(guess.as_slice()))
+    }).cloned()
+}
+
+/// Plays out every candidate in `possible`, picking each guess out of
+/// the candidates still possible with `strategy`, and counts the
+/// guesses each takes. At most `sample` of those candidates are tried
+/// as each guess, scored against each other. Returns `None` if nothing
+/// is possible.
+pub fn outlook(possible: &[Vec<u8>], strategy: Strategy, sample: usize) -> Option<Outlook> {
+    if possible.is_empty() {
+        return None;
+    }
+    let (worst, total) = plan(possible.to_vec(), strategy, sample);
+    Some(Outlook { worst, average: total as f64 / possible.len() as f64 })
+}
+
+/// The most guesses any of `possible` takes, and how many they take
+/// altogether.
+fn plan(possible: Vec<Vec<u8>>, strategy: Strategy, sample: usize) -> (usize, usize) {
+    if possible.len() == 1 {
+        return (1, 1);
+    }
+    let sampled = evenly(&possible, sample);
+    let guess = best_guess(&sampled, &sampled, strategy).unwrap();
+    let (mut worst, mut total) = (1, 0);
+    for (_, group) in partition(&guess, &possible) {
+        // The guess itself is found with it, and the rest of its group
+        // takes at least one more.
+        let rest: Vec<Vec<u8>> = group.into_iter().filter(|code| **code != guess).cloned().collect();
+        if rest.is_empty() {
+            total += 1;
+            continue;
+        }
+        let (group_worst, group_total) = plan(rest.clone(), strategy, sample);
+        worst = worst.max(group_worst + 1);
+        total += group_total + rest.len();
+    }
+    (worst, total)
+}
+
+/// Groups `possible` by the feedback `guess` would get against each.
+fn partition<'a>(guess: &[u8], possible: &'a [Vec<u8>]) -> HashMap<Feedback, Vec<&'a Vec<u8>>> {
+    let mut groups: HashMap<Feedback, Vec<&Vec<u8>>> = HashMap::new();
+    for code in possible {
+        groups.entry(feedback(guess, code)).or_default().push(code);
+    }
+    groups
+}
+
+/// Up to `count` of `items`, spread out evenly.
+pub fn evenly(items: &[Vec<u8>], count: usize) -> Vec<Vec<u8>> {
+    let step = items.len().div_ceil(count.max(1)).max(1);
+    items.iter().step_by(step).cloned().collect()
+}
+
+/// Plays the codebreaker: makes a guess with `strategy`, hands it to
+/// `answer` for feedback, and adds the feedback as rules, until a guess
+/// is right. Returns every guess made, the last of which is the code,
+/// or `Error::Contradiction` once the feedback given rules out every
+/// candidate.
+pub fn play<F>(passcode: &mut Passcode, strategy: Strategy, mut answer: F) -> Result<Vec<Vec<u8>>, Error>
+    where F: FnMut(&[u8]) -> Feedback
+{
+    let mut guesses: Vec<Vec<u8>> = Vec::new();
+    passcode.eliminate_combinations()?;
+    loop {
+        let guess = passcode.next_guess(strategy).ok_or(Error::Contradiction)?;
+        let given = answer(&guess);
+        guesses.push(guess.clone());
+        if is_right(passcode, &guess, given) {
+            return Ok(guesses);
+        }
+        passcode.add_feedback(&guess, given)?;
+        passcode.eliminate_combinations()?;
+        // A guess shorter than the longest codes is only known to be
+        // right once nothing longer is left.
+        if passcode.solution().ok().as_ref() == Some(&guess) {
+            return Ok(guesses);
+        }
+    }
+}
+
+/// Whether `given` says `guess` is the code, which it does when every
+/// position is right and the guess is as long as codes get.
+pub fn is_right(passcode: &Passcode, guess: &[u8], given: Feedback) -> bool {
+    given.exact == guess.len() && given.misplaced == 0 && guess.len() == *passcode.lengths().end()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alphabet::Alphabet;
+
+    fn mastermind() -> Vec<Vec<u8>> {
+        let mut codes = Vec::new();
+        for i in 0..6u32.pow(4) {
+            codes.push((0..4).map(|p| (i / 6u32.pow(p) % 6) as u8).rev().collect());
+        }
+        codes
+    }
+
+    #[test]
+    fn scores() {
+        assert_eq!(feedback(&[1, 2, 3, 4], &[1, 2, 3, 4]), Feedback { exact: 4, misplaced: 0 });
+        assert_eq!(feedback(&[1, 1, 2, 2], &[1, 2, 1, 3]), Feedback { exact: 1, misplaced: 2 });
+        assert_eq!(feedback(&[5, 5, 5, 5], &[1, 2, 3, 5]), Feedback { exact: 1, misplaced: 0 });
+        assert_eq!(feedback(&[1, 2], &[2, 1, 3]), Feedback { exact: 0, misplaced: 2 });
+    }
+
+    #[test]
+    fn knuths_first_guess() {
+        let codes = mastermind();
+        // Guesses of the form 1122 leave at most 256 candidates, better
+        // than any other kind of first guess.
+        let guess = best_guess(&codes, &codes, Strategy::Minimax).unwrap();
+        assert_eq!(guess, vec![0, 0, 1, 1]);
+        let groups = partition(&guess, &codes);
+        assert_eq!(groups.values().map(|group| group.len()).max(), Some(256));
+        assert_eq!(best_guess(&codes, &codes, Strategy::MostParts).map(|guess| partition(&guess, &codes).len()), Some(14));
+    }
+
+    #[test]
+    fn plays_out_every_code() {
+        let codes: Vec<Vec<u8>> = mastermind().into_iter().filter(|code| code[0] == 0 && code[1] < 2).collect();
+        for &strategy in [Strategy::Minimax, Strategy::ExpectedSize, Strategy::MostParts].iter() {
+            // Plays each code out one guess at a time.
+            let guesses: Vec<usize> = codes.iter().map(|code| {
+                let mut possible = codes.clone();
+                let mut guesses = 1;
+                let mut guess = best_guess(&possible, &possible, strategy).unwrap();
+                while guess != *code {
+                    let given = feedback(&guess, code);
+                    possible.retain(|other| feedback(&guess, other) == given);
+                    guess = best_guess(&possible, &possible, strategy).unwrap();
+                    guesses += 1;
+                }
+                guesses
+            }).collect();
+            let average = guesses.iter().sum::<usize>() as f64 / codes.len() as f64;
+            let outlook = outlook(&codes, strategy, codes.len()).unwrap();
+            assert_eq!(outlook.worst, *guesses.iter().max().unwrap());
+            assert!((outlook.average - average).abs() < 1e-9);
+        }
+        assert_eq!(outlook(&[vec![1, 2]], Strategy::Minimax, 10), Some(Outlook { worst: 1, average: 1.0 }));
+    }
+
+    #[test]
+    fn breaks_codes() {
+        for code in &[vec![3, 1, 4, 1], vec![0, 0, 0, 0], vec![5, 4, 3, 2]] {
+            let mut passcode = Passcode::new(4, Alphabet::from_chars("012345").unwrap()).unwrap();
+            let guesses = play(&mut passcode, Strategy::Minimax, |guess| feedback(guess, code)).unwrap();
+            assert_eq!(guesses.last(), Some(code));
+            assert!(guesses.len() <= 6);
+        }
+        let mut passcode = Passcode::new(2, Alphabet::from_chars("012").unwrap()).unwrap();
+        let lie = play(&mut passcode, Strategy::Minimax, |_| Feedback { exact: 0, misplaced: 0 });
+        assert_eq!(lie, Err(Error::Contradiction));
+    }
+}
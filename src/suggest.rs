@@ -0,0 +1,172 @@
+use std::cmp::Ordering;
+use ast::{self, Context};
+use ast::bytecode::Program;
+use ast::predicate;
+use alphabet::Alphabet;
+use lock::Lock;
+use position_name;
+
+/// A yes or no question about the code, written as a rule that can be
+/// added as it is once it's been answered with yes.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Query {
+    pub rule: String,
+    /// The rule to add when the answer is no instead, unless rules have
+    /// no way of saying so.
+    pub if_not: Option<String>,
+    /// How many bits of information the answer is expected to give,
+    /// with every candidate left as likely as any other. A question that
+    /// splits the candidates in half gives 1 bit.
+    pub gain: f64,
+}
+
+/// Writes out the questions of every kind worth asking about a code
+/// whose positions can hold `domains`, of which every candidate has at
+/// least `min_length`:
+///
+/// * comparisons of a position with a value, and of two positions
+/// * the parity of a position
+/// * membership of a position in a pair of values, written as the
+///   quadratic whose roots they are, as rules have no "or"
+/// * the sum of two positions, compared with a value
+/// * the lock's true or false predicates, and how many buttons are
+///   pressed on push-button locks
+/// * the length of the code, when it isn't fixed
+///
+/// Each comes with the rule for the answer no, where there is one.
+/// Positions of push-button locks are buttons, which only the
+/// predicates ask about.
+pub fn templates(lock: &Lock, alphabet: &Alphabet, domains: &[Vec<u8>], min_length: usize) -> Vec<(String, Option<String>)> {
+    let mut rules: Vec<(String, Option<String>)> = Vec::new();
+    let value = |v: u8| match alphabet.symbol_for(v) {
+        Some(symbol) if symbol != v.to_string() && !symbol.contains('\'') => format!("'{}'", symbol),
+        _ => v.to_string(),
+    };
+    if !matches!(*lock, Lock::PushButton { .. }) {
+        for (i, domain) in domains.iter().enumerate() {
+            let x = position_name(i);
+            if domain.len() < 2 {
+                continue;
+            }
+            // A position differs from a value when the square of their
+            // difference is above 0.
+            rules.extend(domain.iter().map(|v| {
+                let square = *v as u32 * *v as u32;
+                let differs = match square {
+                    0 => format!("{} > 0", x),
+                    _ => format!("{} * {} + {} > {} * {}", x, x, square, 2 * *v as u32, x),
+                };
+                (format!("{} = {}", x, value(*v)), Some(differs))
+            }));
+            rules.extend(domain[..domain.len() - 1].iter().map(|v| (format!("{} > {}", x, v), Some(format!("{} < {}", x, *v as u32 + 1)))));
+            if domain.iter().any(|v| v % 2 == 0) && domain.iter().any(|v| v % 2 == 1) {
+                rules.push((format!("{} % 2 = 0", x), Some(format!("{} % 2 = 1", x))));
+            }
+            if domain.len() > 2 {
+                for (j, v1) in domain.iter().enumerate() {
+                    for v2 in domain[j + 1..].iter() {
+                        let (sum, product) = (*v1 as u32 + *v2 as u32, *v1 as u32 * *v2 as u32);
+                        rules.push((match product {
+                            0 => format!("{} * {} = {} * {}", x, x, sum, x),
+                            _ => format!("{} * {} + {} = {} * {}", x, x, product, sum, x),
+                        }, None));
+                    }
+                }
+            }
+        }
+        for i in 0..domains.len() {
+            for j in i + 1..domains.len() {
+                if domains[i].is_empty() || domains[j].is_empty() || domains[i].len() + domains[j].len() < 3 {
+                    continue;
+                }
+                let (x, y) = (position_name(i), position_name(j));
+                rules.push((format!("{} = {}", x, y), Some(format!("{} * {} + {} * {} > 2 * {} * {}", x, x, y, y, x, y))));
+                rules.push((format!("{} > {}", x, y), Some(format!("{} < {} + 1", x, y))));
+                rules.push((format!("{} < {}", x, y), Some(format!("{} + 1 > {}", x, y))));
+                let lowest = domains[i][0] as u32 + domains[j][0] as u32;
+                let highest = *domains[i].last().unwrap() as u32 + *domains[j].last().unwrap() as u32;
+                rules.extend((lowest..=highest).map(|sum| (format!("{} + {} = {}", x, y, sum), None)));
+                rules.extend((lowest..highest).map(|sum| (format!("{} + {} > {}", x, y, sum), Some(format!("{} + {} < {}", x, y, sum + 1)))));
+            }
+        }
+    }
+    rules.extend(predicate::library(lock, alphabet).iter().map(|predicate| {
+        let rule = predicate.render(alphabet);
+        let negated = format!("!{}", rule);
+        (rule, Some(negated))
+    }));
+    if let Lock::PushButton { .. } = *lock {
+        rules.extend((0..=alphabet.len()).map(|count| (format!("count = {}", count), None)));
+        rules.extend((0..alphabet.len()).map(|count| (format!("count > {}", count), Some(format!("count < {}", count + 1)))));
+    }
+    rules.extend((min_length..domains.len()).map(|length| (format!("len > {}", length), Some(format!("len < {}", length + 1)))));
+    rules
+}
+
+/// Scores every rule by how evenly it splits `survivors`, and returns
+/// the `k` that split them most evenly, best first. Rules that every
+/// survivor agrees on, or that can't be parsed, are left out. So is the
+/// rule for the answer no of any that doesn't hold for exactly the
+/// survivors the rule itself doesn't, as arithmetic on a dial wraps
+/// around.
+pub fn rank(rules: Vec<(String, Option<String>)>, context: &Context, survivors: &[Vec<u8>], k: usize) -> Vec<Query> {
+    let total = survivors.len() as f64;
+    let compile = |rule: &str| ast::convert_string_to_ast(rule, context).ok().map(|ast| Program::compile(&ast));
+    let mut queries: Vec<Query> = rules.into_iter().filter_map(|(rule, if_not)| {
+        let program = compile(&rule)?;
+        let answers: Vec<bool> = survivors.iter().map(|survivor| program.holds(survivor)).collect();
+        let yes = answers.iter().filter(|answer| **answer).count();
+        if yes == 0 || yes == survivors.len() {
+            return None;
+        }
+        let if_not = if_not.filter(|if_not| match compile(if_not) {
+            Some(negation) => survivors.iter().zip(answers.iter()).all(|(survivor, answer)| negation.holds(survivor) != *answer),
+            None => false,
+        });
+        Some(Query { rule, if_not, gain: entropy(yes as f64 / total) })
+    }).collect();
+    // A stable sort, so of equally good questions the simplest, which
+    // are written out first, come first.
+    queries.sort_by(|a, b| b.gain.partial_cmp(&a.gain).unwrap_or(Ordering::Equal));
+    queries.truncate(k);
+    queries
+}
+
+/// The entropy of a yes or no answer that's yes with probability `p`,
+/// in bits.
+fn entropy(p: f64) -> f64 {
+    -(p * p.log2() + (1.0 - p) * (1.0 - p).log2())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn templates_of_each_kind() {
+        let alphabet = Alphabet::decimal();
+        let rules = templates(&Lock::Code, &alphabet, &[vec![1, 2, 3], vec![4]], 2);
+        let rules: Vec<&str> = rules.iter().map(|(rule, _)| rule.as_str()).collect();
+        for rule in &["a = 3", "a > 2", "a % 2 = 0", "a * a + 2 = 3 * a", "a = b", "a + b = 7", "a + b > 6"] {
+            assert!(rules.contains(rule), "{} is missing", rule);
+        }
+        assert!(!rules.contains(&"b = 4") && !rules.contains(&"a > 3"));
+        let hex = Alphabet::hexadecimal();
+        assert_eq!(templates(&Lock::Code, &hex, &[vec![10, 11]], 1)[1], ("a = 'b'".to_owned(), Some("a * a + 121 > 22 * a".to_owned())));
+    }
+
+    #[test]
+    fn halves_rank_first() {
+        let context = ast::code_context(1);
+        let survivors: Vec<Vec<u8>> = (0..4).map(|v| vec![v]).collect();
+        let rules = vec![("a = 0", Some("a > 0")), ("a > 9", None), ("a > 1", Some("a < 3")), ("a +", None)];
+        let rules = rules.into_iter().map(|(rule, if_not)| (rule.to_owned(), if_not.map(|r| r.to_owned()))).collect();
+        let queries = rank(rules, &context, &survivors, 5);
+        assert_eq!(queries.iter().map(|q| q.rule.as_str()).collect::<Vec<&str>>(), vec!["a > 1", "a = 0"]);
+        // `a < 3` still holds for 2, which `a > 1` does too.
+        assert_eq!(queries[0].if_not, None);
+        assert_eq!(queries[1].if_not, Some("a > 0".to_owned()));
+        assert!((queries[0].gain - 1.0).abs() < 1e-9);
+        assert!((queries[1].gain - 0.811).abs() < 1e-3);
+    }
+}